@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS user_deposits;
+DROP TABLE IF EXISTS token_acct_balances;
+DROP TABLE IF EXISTS token_accts;
+DROP TABLE IF EXISTS conditional_vaults;
+DROP TABLE IF EXISTS markets;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS tokens;
+DROP TYPE IF EXISTS token_acct_status;
@@ -0,0 +1,88 @@
+-- Tables the watcher reads and writes, matching the table! definitions in src/entities.
+-- Everything is created only if missing, so databases set up before migrations shipped
+-- can adopt them as a baseline.
+
+DO $$ BEGIN
+    CREATE TYPE token_acct_status AS ENUM ('watching', 'enabled', 'disabled');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+CREATE TABLE IF NOT EXISTS tokens (
+    mint_acct VARCHAR PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    symbol VARCHAR NOT NULL,
+    supply NUMERIC NOT NULL,
+    decimals SMALLINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    image_url VARCHAR
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    tx_sig VARCHAR PRIMARY KEY,
+    slot NUMERIC NOT NULL,
+    block_time TIMESTAMPTZ NOT NULL,
+    failed BOOLEAN NOT NULL,
+    payload TEXT NOT NULL,
+    serializer_logic_version SMALLINT NOT NULL,
+    main_ix_type VARCHAR
+);
+
+CREATE INDEX IF NOT EXISTS transactions_block_time_idx ON transactions (block_time);
+
+CREATE TABLE IF NOT EXISTS markets (
+    market_acct VARCHAR PRIMARY KEY,
+    market_type VARCHAR NOT NULL,
+    create_tx_sig VARCHAR NOT NULL,
+    proposal_acct VARCHAR,
+    base_mint_acct VARCHAR NOT NULL,
+    quote_mint_acct VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS conditional_vaults (
+    cond_vault_acct VARCHAR PRIMARY KEY,
+    status VARCHAR,
+    settlement_authority VARCHAR NOT NULL,
+    underlying_mint_acct VARCHAR NOT NULL,
+    underlying_token_acct VARCHAR NOT NULL,
+    nonce VARCHAR,
+    cond_finalize_token_mint_acct VARCHAR NOT NULL,
+    cond_revert_token_mint_acct VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS token_accts (
+    token_acct VARCHAR PRIMARY KEY,
+    mint_acct VARCHAR NOT NULL,
+    owner_acct VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL,
+    updated_at TIMESTAMPTZ,
+    status token_acct_status NOT NULL DEFAULT 'enabled'
+);
+
+CREATE INDEX IF NOT EXISTS token_accts_mint_acct_idx ON token_accts (mint_acct);
+CREATE INDEX IF NOT EXISTS token_accts_status_idx ON token_accts (status);
+
+CREATE TABLE IF NOT EXISTS token_acct_balances (
+    token_acct VARCHAR NOT NULL,
+    mint_acct VARCHAR NOT NULL,
+    owner_acct VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    slot NUMERIC NOT NULL,
+    tx_sig VARCHAR REFERENCES transactions (tx_sig),
+    delta NUMERIC NOT NULL,
+    PRIMARY KEY (token_acct, mint_acct, amount, created_at)
+);
+
+CREATE INDEX IF NOT EXISTS token_acct_balances_token_acct_slot_idx
+    ON token_acct_balances (token_acct, slot);
+
+CREATE TABLE IF NOT EXISTS user_deposits (
+    tx_sig VARCHAR NOT NULL,
+    user_acct VARCHAR NOT NULL,
+    mint_acct VARCHAR NOT NULL,
+    token_amount NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tx_sig, user_acct, mint_acct)
+);
@@ -0,0 +1,9 @@
+-- Owner wallets and mints whose token accts are tracked through one program subscription.
+CREATE TABLE IF NOT EXISTS watch_targets (
+    target_acct VARCHAR NOT NULL,
+    target_type VARCHAR NOT NULL CHECK (target_type IN ('owner', 'mint')),
+    status token_acct_status NOT NULL DEFAULT 'watching',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ,
+    PRIMARY KEY (target_acct, target_type)
+);
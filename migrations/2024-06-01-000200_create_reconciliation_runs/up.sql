@@ -0,0 +1,11 @@
+-- One summary row per run of the balance reconciliation job.
+CREATE TABLE IF NOT EXISTS reconciliation_runs (
+    run_id BIGSERIAL PRIMARY KEY,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    accts_checked INT NOT NULL,
+    accts_corrected INT NOT NULL,
+    accts_missing INT NOT NULL,
+    failed_batches INT NOT NULL,
+    corrected_accts TEXT[] NOT NULL DEFAULT '{}'
+);
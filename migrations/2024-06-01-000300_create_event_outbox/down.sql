@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS transactions_insert_trigger ON transactions;
+DROP TRIGGER IF EXISTS token_accts_status_update_trigger ON token_accts;
+DROP TRIGGER IF EXISTS token_accts_insert_trigger ON token_accts;
+DROP FUNCTION IF EXISTS notify_transactions_insert();
+DROP FUNCTION IF EXISTS notify_token_accts_status_update();
+DROP FUNCTION IF EXISTS notify_token_accts_insert();
+DROP FUNCTION IF EXISTS enqueue_event(TEXT, TEXT);
+DROP TABLE IF EXISTS event_outbox;
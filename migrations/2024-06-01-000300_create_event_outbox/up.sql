@@ -0,0 +1,76 @@
+-- Durable queue for the events the watcher reacts to.
+-- The triggers write a row here and then NOTIFY, so an event raised while the watcher is down
+-- is still picked up on the next start. The NOTIFY only wakes the consumer up early.
+
+CREATE TABLE IF NOT EXISTS event_outbox (
+    outbox_id BIGSERIAL PRIMARY KEY,
+    channel VARCHAR NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    processed_at TIMESTAMPTZ,
+    locked_until TIMESTAMPTZ,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS event_outbox_pending_idx
+    ON event_outbox (outbox_id)
+    WHERE processed_at IS NULL;
+
+CREATE OR REPLACE FUNCTION enqueue_event(event_channel TEXT, event_payload TEXT)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO event_outbox (channel, payload) VALUES (event_channel, event_payload);
+    PERFORM pg_notify(event_channel, event_payload);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_token_accts_insert()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM enqueue_event(
+        'token_accts_insert_channel',
+        json_build_object('token_acct', NEW.token_acct)::TEXT
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_token_accts_status_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status IS DISTINCT FROM OLD.status THEN
+        PERFORM enqueue_event(
+            'token_accts_status_update_channel',
+            json_build_object('token_acct', NEW.token_acct, 'status', NEW.status)::TEXT
+        );
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_transactions_insert()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM enqueue_event(
+        'transactions_insert_channel',
+        json_build_object('tx_sig', NEW.tx_sig)::TEXT
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS token_accts_insert_trigger ON token_accts;
+CREATE TRIGGER token_accts_insert_trigger
+    AFTER INSERT ON token_accts
+    FOR EACH ROW EXECUTE FUNCTION notify_token_accts_insert();
+
+DROP TRIGGER IF EXISTS token_accts_status_update_trigger ON token_accts;
+CREATE TRIGGER token_accts_status_update_trigger
+    AFTER UPDATE OF status ON token_accts
+    FOR EACH ROW EXECUTE FUNCTION notify_token_accts_status_update();
+
+DROP TRIGGER IF EXISTS transactions_insert_trigger ON transactions;
+CREATE TRIGGER transactions_insert_trigger
+    AFTER INSERT ON transactions
+    FOR EACH ROW EXECUTE FUNCTION notify_transactions_insert();
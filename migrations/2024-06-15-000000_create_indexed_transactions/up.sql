@@ -0,0 +1,9 @@
+-- Which transactions have been through the indexers, per indexer version.
+CREATE TABLE IF NOT EXISTS indexed_transactions (
+    tx_sig VARCHAR NOT NULL REFERENCES transactions (tx_sig),
+    indexer_version SMALLINT NOT NULL,
+    outcome VARCHAR NOT NULL CHECK (outcome IN ('indexed', 'failed', 'unhandled')),
+    error TEXT,
+    indexed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tx_sig, indexer_version)
+);
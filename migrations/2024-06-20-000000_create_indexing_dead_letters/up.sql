@@ -0,0 +1,14 @@
+-- Transactions whose indexing failed, waiting to be retried or, once next_retry_at is NULL, looked at.
+CREATE TABLE IF NOT EXISTS indexing_dead_letters (
+    tx_sig VARCHAR PRIMARY KEY REFERENCES transactions (tx_sig),
+    error_kind VARCHAR NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 1,
+    first_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    next_retry_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS indexing_dead_letters_next_retry_at_idx
+    ON indexing_dead_letters (next_retry_at)
+    WHERE next_retry_at IS NOT NULL;
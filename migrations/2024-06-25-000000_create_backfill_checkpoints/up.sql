@@ -0,0 +1,13 @@
+-- How far a backfill got, so it resumes where it stopped after a crash or restart.
+CREATE TABLE IF NOT EXISTS backfill_checkpoints (
+    job VARCHAR PRIMARY KEY,
+    params TEXT NOT NULL,
+    last_slot NUMERIC,
+    last_tx_sig VARCHAR,
+    indexed INT NOT NULL DEFAULT 0,
+    failed INT NOT NULL DEFAULT 0,
+    skipped INT NOT NULL DEFAULT 0,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
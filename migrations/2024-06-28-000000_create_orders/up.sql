@@ -0,0 +1,20 @@
+-- Openbook orders placed and cancelled, one row per transaction.
+CREATE TABLE IF NOT EXISTS orders (
+    order_tx_sig VARCHAR PRIMARY KEY REFERENCES transactions (tx_sig),
+    action VARCHAR NOT NULL CHECK (action IN ('place', 'cancel')),
+    market_acct VARCHAR NOT NULL,
+    actor_acct VARCHAR NOT NULL,
+    open_orders_acct VARCHAR,
+    side VARCHAR CHECK (side IN ('bid', 'ask')),
+    price_lots NUMERIC,
+    base_lots NUMERIC,
+    quote_lots NUMERIC,
+    client_order_id VARCHAR,
+    order_id VARCHAR,
+    order_type VARCHAR,
+    slot NUMERIC NOT NULL,
+    order_time TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS orders_market_acct_idx ON orders (market_acct, slot);
+CREATE INDEX IF NOT EXISTS orders_actor_acct_idx ON orders (actor_acct, slot);
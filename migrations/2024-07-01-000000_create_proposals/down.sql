@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS markets_proposal_acct_idx;
+DROP TABLE IF EXISTS proposals;
@@ -0,0 +1,21 @@
+-- Autocrat proposals and the conditional vaults and markets they trade on.
+CREATE TABLE IF NOT EXISTS proposals (
+    proposal_acct VARCHAR PRIMARY KEY,
+    dao_acct VARCHAR NOT NULL,
+    proposer_acct VARCHAR NOT NULL,
+    description_url VARCHAR,
+    base_pass_vault_acct VARCHAR,
+    base_fail_vault_acct VARCHAR,
+    quote_pass_vault_acct VARCHAR,
+    quote_fail_vault_acct VARCHAR,
+    pass_market_acct VARCHAR,
+    fail_market_acct VARCHAR,
+    status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'passed', 'failed')),
+    init_tx_sig VARCHAR NOT NULL REFERENCES transactions (tx_sig),
+    init_slot NUMERIC NOT NULL,
+    initialized_at TIMESTAMPTZ NOT NULL,
+    finalize_tx_sig VARCHAR REFERENCES transactions (tx_sig),
+    finalized_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS markets_proposal_acct_idx ON markets (proposal_acct);
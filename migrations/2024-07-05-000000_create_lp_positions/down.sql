@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS lp_positions;
+DROP TABLE IF EXISTS lp_position_events;
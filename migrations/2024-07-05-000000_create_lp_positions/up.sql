@@ -0,0 +1,34 @@
+-- Every AMM addLiquidity and removeLiquidity, one row per tx and user.
+CREATE TABLE IF NOT EXISTS lp_position_events (
+    tx_sig VARCHAR NOT NULL REFERENCES transactions (tx_sig),
+    user_acct VARCHAR NOT NULL,
+    amm_acct VARCHAR NOT NULL,
+    lp_mint_acct VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL CHECK (kind IN ('deposit', 'withdrawal')),
+    base_amount NUMERIC NOT NULL,
+    quote_amount NUMERIC NOT NULL,
+    lp_amount NUMERIC NOT NULL,
+    slot NUMERIC NOT NULL,
+    block_time TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (tx_sig, user_acct, amm_acct)
+);
+
+CREATE INDEX IF NOT EXISTS lp_position_events_position_idx
+    ON lp_position_events (user_acct, amm_acct, slot);
+
+-- Per user per AMM totals, folded from lp_position_events.
+CREATE TABLE IF NOT EXISTS lp_positions (
+    user_acct VARCHAR NOT NULL,
+    amm_acct VARCHAR NOT NULL,
+    lp_mint_acct VARCHAR NOT NULL,
+    lp_tokens_minted NUMERIC NOT NULL DEFAULT 0,
+    lp_tokens_burned NUMERIC NOT NULL DEFAULT 0,
+    base_deposited NUMERIC NOT NULL DEFAULT 0,
+    quote_deposited NUMERIC NOT NULL DEFAULT 0,
+    base_withdrawn NUMERIC NOT NULL DEFAULT 0,
+    quote_withdrawn NUMERIC NOT NULL DEFAULT 0,
+    base_cost_basis NUMERIC NOT NULL DEFAULT 0,
+    quote_cost_basis NUMERIC NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_acct, amm_acct)
+);
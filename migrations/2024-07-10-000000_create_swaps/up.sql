@@ -0,0 +1,17 @@
+-- AMM swaps, one row per transaction.
+CREATE TABLE IF NOT EXISTS swaps (
+    swap_tx_sig VARCHAR PRIMARY KEY REFERENCES transactions (tx_sig),
+    market_acct VARCHAR NOT NULL,
+    user_acct VARCHAR NOT NULL,
+    direction VARCHAR NOT NULL CHECK (direction IN ('buy', 'sell')),
+    input_mint_acct VARCHAR NOT NULL,
+    output_mint_acct VARCHAR NOT NULL,
+    input_amount NUMERIC NOT NULL,
+    output_amount NUMERIC NOT NULL,
+    price NUMERIC,
+    slot NUMERIC NOT NULL,
+    block_time TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS swaps_market_acct_idx ON swaps (market_acct, block_time);
+CREATE INDEX IF NOT EXISTS swaps_user_acct_idx ON swaps (user_acct, block_time);
@@ -0,0 +1 @@
+ALTER TABLE reconciliation_runs DROP COLUMN IF EXISTS accts_unreadable;
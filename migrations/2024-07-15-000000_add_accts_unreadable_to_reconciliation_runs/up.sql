@@ -0,0 +1,2 @@
+-- Accts a reconciliation run could not read as spl token accounts, e.g. reassigned ones.
+ALTER TABLE reconciliation_runs ADD COLUMN IF NOT EXISTS accts_unreadable INT NOT NULL DEFAULT 0;
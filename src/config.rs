@@ -0,0 +1,572 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+
+use crate::entities::transactions::InstructionType;
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_POOL_MAX_SIZE: usize = 8;
+const DEFAULT_POOL_WAIT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_POOL_CREATE_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_POOL_RECYCLE_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_COMMITMENT: &str = "confirmed";
+const DEFAULT_BACKFILL_WINDOW_DAYS: i64 = 30;
+const DEFAULT_BACKFILL_PAGE_SIZE: i64 = 200;
+const DEFAULT_BODY_SIZE_LIMIT_BYTES: u64 = 1024 * 16;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_RECONCILIATION_INTERVAL_SECS: u64 = 300;
+const DEFAULT_GAP_BACKFILL_MAX_SIGNATURES: usize = 1000;
+const DEFAULT_SUBSCRIPTION_STALE_AFTER_SECS: u64 = 600;
+const DEFAULT_OUTBOX_POLL_INTERVAL_SECS: u64 = 5;
+const DEFAULT_RECONNECT_BACKOFF_INITIAL_MS: u64 = 500;
+const DEFAULT_RECONNECT_BACKOFF_MAX_SECS: u64 = 60;
+const DEFAULT_TRANSACTION_WORKERS: usize = 2;
+const DEFAULT_TOKEN_ACCT_WORKERS: usize = 2;
+const DEFAULT_ACCOUNT_UPDATE_WORKERS: usize = 4;
+const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 256;
+const DEFAULT_ACCOUNT_UPDATE_OVERFLOW: &str = "block";
+const DEFAULT_RUN_MIGRATIONS: bool = true;
+const DEFAULT_FORCE_REINDEX: bool = false;
+const DEFAULT_DEAD_LETTER_RETRY_INTERVAL_SECS: u64 = 60;
+
+/**
+ * Service configuration, loaded once at startup.
+ * Values come from an optional TOML file (path in CONFIG_FILE) and are overridden by env vars.
+ */
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    pub rpc_endpoint_http: String,
+    pub rpc_endpoint_wss: String,
+    pub auth_service_url: String,
+    pub port: u16,
+    pub pool: PoolConfig,
+    pub commitment: CommitmentConfig,
+    pub backfill: BackfillConfig,
+    /// empty means any origin is allowed
+    pub cors_allowed_origins: Vec<String>,
+    pub body_size_limit_bytes: u64,
+    /// how long in-flight work gets to drain once a shutdown signal arrives
+    pub shutdown_timeout: Duration,
+    /// first delay before reconnecting the websocket or db listener, doubled on each failed attempt
+    pub reconnect_backoff_initial: Duration,
+    pub reconnect_backoff_max: Duration,
+    /// how often Watching balances are compared against chain state
+    pub reconciliation_interval: Duration,
+    /// cap on signatures replayed when filling a token acct's balance gap
+    pub gap_backfill_max_signatures: usize,
+    /// how long an account subscription can go without updates before its balance is checked over http
+    pub subscription_stale_after: Duration,
+    /// fallback poll of the event outbox for when a NOTIFY hint is missed
+    pub outbox_poll_interval: Duration,
+    /// concurrent handlers per event kind, which all share the db pool
+    pub transaction_workers: usize,
+    pub token_acct_workers: usize,
+    pub account_update_workers: usize,
+    /// events queued per kind before the overflow policy applies
+    pub event_queue_capacity: usize,
+    pub account_update_overflow: OverflowPolicy,
+    /// apply pending schema migrations on startup
+    pub run_migrations: bool,
+    /// re-run the backfill on txs the indexing ledger already has at the current indexer version
+    pub force_reindex: bool,
+    /// how often dead lettered txs that are due are indexed again
+    pub dead_letter_retry_interval: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_size: usize,
+    pub wait_timeout: Duration,
+    pub create_timeout: Duration,
+    pub recycle_timeout: Duration,
+}
+
+/**
+ * Which transactions the backfill indexes. Without a start slot or time it covers the last
+ * `window_days`; bounds are inclusive for slots and start inclusive, end exclusive for times.
+ */
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    pub window_days: i64,
+    pub start_slot: Option<i64>,
+    pub end_slot: Option<i64>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// empty means every instruction type
+    pub ix_types: Vec<InstructionType>,
+    /// transactions loaded per page, and between checkpoints
+    pub page_size: i64,
+}
+
+impl BackfillConfig {
+    /// Identifies the range and filter, so a checkpoint is only resumed by the same backfill.
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "window_days={} start_slot={:?} end_slot={:?} start_time={:?} end_time={:?} ix_types={:?}",
+            self.window_days,
+            self.start_slot,
+            self.end_slot,
+            self.start_time,
+            self.end_time,
+            self.ix_types
+        )
+    }
+}
+
+/// What a worker pool does with a job when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    /// wait for room, slowing down whatever is producing the events
+    Block,
+    /// drop the job and count it
+    Drop,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop" => Ok(OverflowPolicy::Drop),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FileConfig {
+    database_url: Option<String>,
+    rpc_endpoint_http: Option<String>,
+    rpc_endpoint_wss: Option<String>,
+    auth_service_url: Option<String>,
+    port: Option<u16>,
+    pool_max_size: Option<usize>,
+    pool_wait_timeout_secs: Option<u64>,
+    pool_create_timeout_secs: Option<u64>,
+    pool_recycle_timeout_secs: Option<u64>,
+    commitment: Option<String>,
+    backfill_window_days: Option<i64>,
+    backfill_start_slot: Option<i64>,
+    backfill_end_slot: Option<i64>,
+    backfill_start_time: Option<String>,
+    backfill_end_time: Option<String>,
+    backfill_ix_types: Option<Vec<String>>,
+    backfill_page_size: Option<i64>,
+    cors_allowed_origins: Option<Vec<String>>,
+    body_size_limit_bytes: Option<u64>,
+    shutdown_timeout_secs: Option<u64>,
+    reconnect_backoff_initial_ms: Option<u64>,
+    reconnect_backoff_max_secs: Option<u64>,
+    reconciliation_interval_secs: Option<u64>,
+    gap_backfill_max_signatures: Option<usize>,
+    subscription_stale_after_secs: Option<u64>,
+    outbox_poll_interval_secs: Option<u64>,
+    transaction_workers: Option<usize>,
+    token_acct_workers: Option<usize>,
+    account_update_workers: Option<usize>,
+    event_queue_capacity: Option<usize>,
+    account_update_overflow: Option<String>,
+    run_migrations: Option<bool>,
+    force_reindex: Option<bool>,
+    dead_letter_retry_interval_secs: Option<u64>,
+}
+
+/// Every problem found while loading, so a bad deploy is reported in one go.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenv::dotenv().ok();
+        let mut problems = vec![];
+
+        let file_config = match env::var("CONFIG_FILE") {
+            Ok(path) => match fs::read_to_string(&path) {
+                Ok(contents) => match toml::from_str::<FileConfig>(&contents) {
+                    Ok(file_config) => file_config,
+                    Err(e) => {
+                        problems.push(format!("could not parse config file {}: {}", path, e));
+                        FileConfig::default()
+                    }
+                },
+                Err(e) => {
+                    problems.push(format!("could not read config file {}: {}", path, e));
+                    FileConfig::default()
+                }
+            },
+            Err(_) => FileConfig::default(),
+        };
+
+        Config::from_sources(&|key| env::var(key).ok(), file_config, problems)
+    }
+
+    /// Builds the config from env vars looked up through `env_var`, falling back to the file.
+    fn from_sources(
+        env_var: &dyn Fn(&str) -> Option<String>,
+        file_config: FileConfig,
+        mut problems: Vec<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut loader = Loader {
+            env_var,
+            problems: &mut problems,
+        };
+
+        let database_url = loader.required("DATABASE_URL", file_config.database_url);
+        let rpc_endpoint_http = loader.required("RPC_ENDPOINT_HTTP", file_config.rpc_endpoint_http);
+        let rpc_endpoint_wss = loader.required("RPC_ENDPOINT_WSS", file_config.rpc_endpoint_wss);
+        let auth_service_url = loader.required("AUTH_SERVICE_URL", file_config.auth_service_url);
+        let port = loader.parsed("PORT", file_config.port, DEFAULT_PORT);
+        let pool_max_size = loader.parsed(
+            "DATABASE_POOL_MAX_SIZE",
+            file_config.pool_max_size,
+            DEFAULT_POOL_MAX_SIZE,
+        );
+        let pool_wait_timeout_secs = loader.parsed(
+            "DATABASE_POOL_WAIT_TIMEOUT_SECS",
+            file_config.pool_wait_timeout_secs,
+            DEFAULT_POOL_WAIT_TIMEOUT_SECS,
+        );
+        let pool_create_timeout_secs = loader.parsed(
+            "DATABASE_POOL_CREATE_TIMEOUT_SECS",
+            file_config.pool_create_timeout_secs,
+            DEFAULT_POOL_CREATE_TIMEOUT_SECS,
+        );
+        let pool_recycle_timeout_secs = loader.parsed(
+            "DATABASE_POOL_RECYCLE_TIMEOUT_SECS",
+            file_config.pool_recycle_timeout_secs,
+            DEFAULT_POOL_RECYCLE_TIMEOUT_SECS,
+        );
+        let commitment_str = loader.parsed(
+            "RPC_COMMITMENT",
+            file_config.commitment,
+            DEFAULT_COMMITMENT.to_string(),
+        );
+        let backfill_window_days = loader.parsed(
+            "BACKFILL_WINDOW_DAYS",
+            file_config.backfill_window_days,
+            DEFAULT_BACKFILL_WINDOW_DAYS,
+        );
+        let backfill_start_slot =
+            loader.optional("BACKFILL_START_SLOT", file_config.backfill_start_slot);
+        let backfill_end_slot = loader.optional("BACKFILL_END_SLOT", file_config.backfill_end_slot);
+        let backfill_start_time_str =
+            loader.optional("BACKFILL_START_TIME", file_config.backfill_start_time);
+        let backfill_end_time_str =
+            loader.optional("BACKFILL_END_TIME", file_config.backfill_end_time);
+        let backfill_ix_types_strs: Vec<String> = match env_var("BACKFILL_IX_TYPES") {
+            Some(ix_types) => ix_types
+                .split(',')
+                .map(|ix_type| ix_type.trim().to_string())
+                .filter(|ix_type| !ix_type.is_empty())
+                .collect(),
+            None => file_config.backfill_ix_types.unwrap_or_default(),
+        };
+        let backfill_page_size = loader.parsed(
+            "BACKFILL_PAGE_SIZE",
+            file_config.backfill_page_size,
+            DEFAULT_BACKFILL_PAGE_SIZE,
+        );
+        let cors_allowed_origins = match env_var("CORS_ALLOWED_ORIGINS") {
+            Some(origins) => origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+            None => file_config.cors_allowed_origins.unwrap_or_default(),
+        };
+        let body_size_limit_bytes = loader.parsed(
+            "BODY_SIZE_LIMIT_BYTES",
+            file_config.body_size_limit_bytes,
+            DEFAULT_BODY_SIZE_LIMIT_BYTES,
+        );
+        let shutdown_timeout_secs = loader.parsed(
+            "SHUTDOWN_TIMEOUT_SECS",
+            file_config.shutdown_timeout_secs,
+            DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+        );
+        let reconnect_backoff_initial_ms = loader.parsed(
+            "RECONNECT_BACKOFF_INITIAL_MS",
+            file_config.reconnect_backoff_initial_ms,
+            DEFAULT_RECONNECT_BACKOFF_INITIAL_MS,
+        );
+        let reconnect_backoff_max_secs = loader.parsed(
+            "RECONNECT_BACKOFF_MAX_SECS",
+            file_config.reconnect_backoff_max_secs,
+            DEFAULT_RECONNECT_BACKOFF_MAX_SECS,
+        );
+        let reconciliation_interval_secs = loader.parsed(
+            "RECONCILIATION_INTERVAL_SECS",
+            file_config.reconciliation_interval_secs,
+            DEFAULT_RECONCILIATION_INTERVAL_SECS,
+        );
+        let gap_backfill_max_signatures = loader.parsed(
+            "GAP_BACKFILL_MAX_SIGNATURES",
+            file_config.gap_backfill_max_signatures,
+            DEFAULT_GAP_BACKFILL_MAX_SIGNATURES,
+        );
+        let subscription_stale_after_secs = loader.parsed(
+            "SUBSCRIPTION_STALE_AFTER_SECS",
+            file_config.subscription_stale_after_secs,
+            DEFAULT_SUBSCRIPTION_STALE_AFTER_SECS,
+        );
+        let outbox_poll_interval_secs = loader.parsed(
+            "OUTBOX_POLL_INTERVAL_SECS",
+            file_config.outbox_poll_interval_secs,
+            DEFAULT_OUTBOX_POLL_INTERVAL_SECS,
+        );
+        let transaction_workers = loader.parsed(
+            "TRANSACTION_WORKERS",
+            file_config.transaction_workers,
+            DEFAULT_TRANSACTION_WORKERS,
+        );
+        let token_acct_workers = loader.parsed(
+            "TOKEN_ACCT_WORKERS",
+            file_config.token_acct_workers,
+            DEFAULT_TOKEN_ACCT_WORKERS,
+        );
+        let account_update_workers = loader.parsed(
+            "ACCOUNT_UPDATE_WORKERS",
+            file_config.account_update_workers,
+            DEFAULT_ACCOUNT_UPDATE_WORKERS,
+        );
+        let event_queue_capacity = loader.parsed(
+            "EVENT_QUEUE_CAPACITY",
+            file_config.event_queue_capacity,
+            DEFAULT_EVENT_QUEUE_CAPACITY,
+        );
+        let account_update_overflow_str = loader.parsed(
+            "ACCOUNT_UPDATE_OVERFLOW",
+            file_config.account_update_overflow,
+            DEFAULT_ACCOUNT_UPDATE_OVERFLOW.to_string(),
+        );
+        let run_migrations = loader.parsed(
+            "RUN_MIGRATIONS",
+            file_config.run_migrations,
+            DEFAULT_RUN_MIGRATIONS,
+        );
+        let force_reindex = loader.parsed(
+            "FORCE_REINDEX",
+            file_config.force_reindex,
+            DEFAULT_FORCE_REINDEX,
+        );
+        let dead_letter_retry_interval_secs = loader.parsed(
+            "DEAD_LETTER_RETRY_INTERVAL_SECS",
+            file_config.dead_letter_retry_interval_secs,
+            DEFAULT_DEAD_LETTER_RETRY_INTERVAL_SECS,
+        );
+
+        let commitment = match CommitmentLevel::from_str(&commitment_str) {
+            Ok(level @ CommitmentLevel::Processed)
+            | Ok(level @ CommitmentLevel::Confirmed)
+            | Ok(level @ CommitmentLevel::Finalized) => CommitmentConfig { commitment: level },
+            _ => {
+                problems.push(format!(
+                    "RPC_COMMITMENT must be one of processed, confirmed, finalized; got {}",
+                    commitment_str
+                ));
+                CommitmentConfig::confirmed()
+            }
+        };
+        if pool_max_size == 0 {
+            problems.push("DATABASE_POOL_MAX_SIZE must be greater than 0".to_string());
+        }
+        if backfill_window_days <= 0 {
+            problems.push("BACKFILL_WINDOW_DAYS must be greater than 0".to_string());
+        }
+        let mut parse_time = |key: &str, value: Option<String>| {
+            value.and_then(|value| match DateTime::parse_from_rfc3339(&value) {
+                Ok(time) => Some(time.with_timezone(&Utc)),
+                Err(_) => {
+                    problems.push(format!("{} must be an RFC 3339 time; got {}", key, value));
+                    None
+                }
+            })
+        };
+        let backfill_start_time = parse_time("BACKFILL_START_TIME", backfill_start_time_str);
+        let backfill_end_time = parse_time("BACKFILL_END_TIME", backfill_end_time_str);
+        if let (Some(start), Some(end)) = (backfill_start_slot, backfill_end_slot) {
+            if start > end {
+                problems
+                    .push("BACKFILL_START_SLOT must not be after BACKFILL_END_SLOT".to_string());
+            }
+        }
+        if let (Some(start), Some(end)) = (backfill_start_time, backfill_end_time) {
+            if start >= end {
+                problems.push("BACKFILL_START_TIME must be before BACKFILL_END_TIME".to_string());
+            }
+        }
+        let mut backfill_ix_types = vec![];
+        for ix_type_str in backfill_ix_types_strs {
+            match InstructionType::from_str(&ix_type_str) {
+                Ok(ix_type) => backfill_ix_types.push(ix_type),
+                Err(e) => problems.push(format!("BACKFILL_IX_TYPES has an {}", e)),
+            }
+        }
+        if backfill_page_size <= 0 {
+            problems.push("BACKFILL_PAGE_SIZE must be greater than 0".to_string());
+        }
+        if body_size_limit_bytes == 0 {
+            problems.push("BODY_SIZE_LIMIT_BYTES must be greater than 0".to_string());
+        }
+        if reconciliation_interval_secs == 0 {
+            problems.push("RECONCILIATION_INTERVAL_SECS must be greater than 0".to_string());
+        }
+        if dead_letter_retry_interval_secs == 0 {
+            problems.push("DEAD_LETTER_RETRY_INTERVAL_SECS must be greater than 0".to_string());
+        }
+        if subscription_stale_after_secs == 0 {
+            problems.push("SUBSCRIPTION_STALE_AFTER_SECS must be greater than 0".to_string());
+        }
+        if outbox_poll_interval_secs == 0 {
+            problems.push("OUTBOX_POLL_INTERVAL_SECS must be greater than 0".to_string());
+        }
+        let account_update_overflow = match OverflowPolicy::from_str(&account_update_overflow_str) {
+            Ok(policy) => policy,
+            Err(()) => {
+                problems.push(format!(
+                    "ACCOUNT_UPDATE_OVERFLOW must be one of block, drop; got {}",
+                    account_update_overflow_str
+                ));
+                OverflowPolicy::Drop
+            }
+        };
+        if transaction_workers == 0 {
+            problems.push("TRANSACTION_WORKERS must be greater than 0".to_string());
+        }
+        if token_acct_workers == 0 {
+            problems.push("TOKEN_ACCT_WORKERS must be greater than 0".to_string());
+        }
+        if account_update_workers == 0 {
+            problems.push("ACCOUNT_UPDATE_WORKERS must be greater than 0".to_string());
+        }
+        if event_queue_capacity == 0 {
+            problems.push("EVENT_QUEUE_CAPACITY must be greater than 0".to_string());
+        }
+        if reconnect_backoff_initial_ms == 0 {
+            problems.push("RECONNECT_BACKOFF_INITIAL_MS must be greater than 0".to_string());
+        }
+        if reconnect_backoff_initial_ms > reconnect_backoff_max_secs * 1000 {
+            problems.push(
+                "RECONNECT_BACKOFF_INITIAL_MS must not exceed RECONNECT_BACKOFF_MAX_SECS"
+                    .to_string(),
+            );
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError { problems });
+        }
+
+        Ok(Config {
+            database_url,
+            rpc_endpoint_http,
+            rpc_endpoint_wss,
+            auth_service_url,
+            port,
+            pool: PoolConfig {
+                max_size: pool_max_size,
+                wait_timeout: Duration::from_secs(pool_wait_timeout_secs),
+                create_timeout: Duration::from_secs(pool_create_timeout_secs),
+                recycle_timeout: Duration::from_secs(pool_recycle_timeout_secs),
+            },
+            commitment,
+            backfill: BackfillConfig {
+                window_days: backfill_window_days,
+                start_slot: backfill_start_slot,
+                end_slot: backfill_end_slot,
+                start_time: backfill_start_time,
+                end_time: backfill_end_time,
+                ix_types: backfill_ix_types,
+                page_size: backfill_page_size,
+            },
+            cors_allowed_origins,
+            body_size_limit_bytes,
+            shutdown_timeout: Duration::from_secs(shutdown_timeout_secs),
+            reconnect_backoff_initial: Duration::from_millis(reconnect_backoff_initial_ms),
+            reconnect_backoff_max: Duration::from_secs(reconnect_backoff_max_secs),
+            reconciliation_interval: Duration::from_secs(reconciliation_interval_secs),
+            gap_backfill_max_signatures,
+            subscription_stale_after: Duration::from_secs(subscription_stale_after_secs),
+            outbox_poll_interval: Duration::from_secs(outbox_poll_interval_secs),
+            transaction_workers,
+            token_acct_workers,
+            account_update_workers,
+            event_queue_capacity,
+            account_update_overflow,
+            run_migrations,
+            force_reindex,
+            dead_letter_retry_interval: Duration::from_secs(dead_letter_retry_interval_secs),
+        })
+    }
+}
+
+struct Loader<'a> {
+    env_var: &'a dyn Fn(&str) -> Option<String>,
+    problems: &'a mut Vec<String>,
+}
+
+impl Loader<'_> {
+    fn required(&mut self, key: &str, file_value: Option<String>) -> String {
+        match (self.env_var)(key).or(file_value) {
+            Some(value) if !value.is_empty() => value,
+            _ => {
+                self.problems.push(format!("{} must be set", key));
+                String::default()
+            }
+        }
+    }
+
+    fn parsed<T: FromStr>(&mut self, key: &str, file_value: Option<T>, default: T) -> T {
+        match (self.env_var)(key) {
+            Some(value) => match value.parse::<T>() {
+                Ok(parsed) => parsed,
+                Err(_) => {
+                    self.problems
+                        .push(format!("{} has an invalid value: {}", key, value));
+                    default
+                }
+            },
+            None => file_value.unwrap_or(default),
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, key: &str, file_value: Option<T>) -> Option<T> {
+        match (self.env_var)(key) {
+            Some(value) => match value.parse::<T>() {
+                Ok(parsed) => Some(parsed),
+                Err(_) => {
+                    self.problems
+                        .push(format!("{} has an invalid value: {}", key, value));
+                    None
+                }
+            },
+            None => file_value,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "config_test.rs"]
+mod tests;
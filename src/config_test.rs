@@ -0,0 +1,112 @@
+use super::*;
+use std::collections::HashMap;
+
+fn load_with(env_vars: &[(&str, &str)], file_config: FileConfig) -> Result<Config, ConfigError> {
+    let env_vars: HashMap<String, String> = env_vars
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Config::from_sources(&|key| env_vars.get(key).cloned(), file_config, vec![])
+}
+
+const REQUIRED: [(&str, &str); 4] = [
+    ("DATABASE_URL", "postgres://localhost/test"),
+    ("RPC_ENDPOINT_HTTP", "http://localhost:8899"),
+    ("RPC_ENDPOINT_WSS", "ws://localhost:8900"),
+    ("AUTH_SERVICE_URL", "http://localhost:3000"),
+];
+
+#[test]
+fn test_load_defaults() {
+    let config = load_with(&REQUIRED, FileConfig::default()).expect("config should load");
+
+    assert_eq!(config.database_url, "postgres://localhost/test");
+    assert_eq!(config.port, DEFAULT_PORT);
+    assert_eq!(config.pool.max_size, DEFAULT_POOL_MAX_SIZE);
+    assert_eq!(
+        config.pool.wait_timeout,
+        Duration::from_secs(DEFAULT_POOL_WAIT_TIMEOUT_SECS)
+    );
+    assert_eq!(config.commitment, CommitmentConfig::confirmed());
+    assert_eq!(config.backfill.window_days, DEFAULT_BACKFILL_WINDOW_DAYS);
+    assert_eq!(config.backfill.page_size, DEFAULT_BACKFILL_PAGE_SIZE);
+    assert!(config.backfill.start_slot.is_none());
+    assert!(config.backfill.ix_types.is_empty());
+    assert!(config.cors_allowed_origins.is_empty());
+    assert_eq!(
+        config.reconnect_backoff_initial,
+        Duration::from_millis(DEFAULT_RECONNECT_BACKOFF_INITIAL_MS)
+    );
+    assert_eq!(config.event_queue_capacity, DEFAULT_EVENT_QUEUE_CAPACITY);
+    assert_eq!(config.account_update_overflow, OverflowPolicy::Block);
+    assert!(config.run_migrations);
+    assert!(!config.force_reindex);
+}
+
+#[test]
+fn test_load_reports_every_missing_required_value() {
+    let err = load_with(&[], FileConfig::default()).expect_err("config should not load");
+
+    assert_eq!(
+        err.problems,
+        vec![
+            "DATABASE_URL must be set",
+            "RPC_ENDPOINT_HTTP must be set",
+            "RPC_ENDPOINT_WSS must be set",
+            "AUTH_SERVICE_URL must be set",
+        ]
+    );
+}
+
+#[test]
+fn test_load_env_overrides_file() {
+    let file_config = FileConfig {
+        port: Some(9000),
+        transaction_workers: Some(3),
+        cors_allowed_origins: Some(vec!["https://file.example".to_string()]),
+        ..FileConfig::default()
+    };
+    let mut env_vars = REQUIRED.to_vec();
+    env_vars.push(("PORT", "9100"));
+    env_vars.push((
+        "CORS_ALLOWED_ORIGINS",
+        "https://a.example, https://b.example",
+    ));
+
+    let config = load_with(&env_vars, file_config).expect("config should load");
+
+    assert_eq!(config.port, 9100);
+    assert_eq!(config.transaction_workers, 3);
+    assert_eq!(
+        config.cors_allowed_origins,
+        vec!["https://a.example", "https://b.example"]
+    );
+}
+
+#[test]
+fn test_load_reports_invalid_values() {
+    let mut env_vars = REQUIRED.to_vec();
+    env_vars.push(("PORT", "not-a-port"));
+    env_vars.push(("RPC_COMMITMENT", "eventually"));
+    env_vars.push(("TRANSACTION_WORKERS", "0"));
+    env_vars.push(("BACKFILL_START_SLOT", "200"));
+    env_vars.push(("BACKFILL_END_SLOT", "100"));
+
+    let err = load_with(&env_vars, FileConfig::default()).expect_err("config should not load");
+
+    assert_eq!(err.problems.len(), 4, "{:?}", err.problems);
+    assert!(err
+        .problems
+        .contains(&"PORT has an invalid value: not-a-port".to_string()));
+    assert!(err
+        .problems
+        .iter()
+        .any(|problem| problem.starts_with("RPC_COMMITMENT")));
+    assert!(err
+        .problems
+        .contains(&"TRANSACTION_WORKERS must be greater than 0".to_string()));
+    assert!(err
+        .problems
+        .iter()
+        .any(|problem| problem.starts_with("BACKFILL_START_SLOT")));
+}
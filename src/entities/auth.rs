@@ -1,3 +1,6 @@
+// request/response shapes for the auth service, only used once session validation is re-enabled
+#![allow(dead_code)]
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
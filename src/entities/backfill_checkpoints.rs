@@ -0,0 +1,39 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+
+table! {
+    backfill_checkpoints (job) {
+        job -> Varchar,
+        params -> Text,
+        last_slot -> Nullable<Numeric>,
+        last_tx_sig -> Nullable<Varchar>,
+        indexed -> Int4,
+        failed -> Int4,
+        skipped -> Int4,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+/**
+ * Progress of a backfill, keyed by job. The cursor is the (slot, tx_sig) of the last
+ * transaction handled, and is only resumed by a backfill with the same params.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = backfill_checkpoints, treat_none_as_null = true)]
+pub struct BackfillCheckpoint {
+    pub job: String,
+    /// the range and filter the backfill runs with, see BackfillConfig::fingerprint
+    pub params: String,
+    pub last_slot: Option<BigDecimal>,
+    pub last_tx_sig: Option<String>,
+    pub indexed: i32,
+    /// txs with failed indexers or that could not be indexed at all
+    pub failed: i32,
+    /// txs the indexing ledger already had
+    pub skipped: i32,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
@@ -14,6 +14,7 @@ table! {
 }
 
 #[derive(Queryable, Clone)]
+#[allow(dead_code)]
 pub struct ConditionalVault {
     pub cond_vault_acct: String,
     pub status: Option<String>,
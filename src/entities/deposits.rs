@@ -1,8 +1,8 @@
-use chrono::{DateTime, Utc};
 use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
 
 table! {
-    user_deposits (user_acct) {
+    user_deposits (tx_sig, user_acct, mint_acct) {
         user_acct -> Varchar,
         token_amount -> Numeric,
         mint_acct -> Varchar,
@@ -23,7 +23,12 @@ pub struct UserDeposit {
 }
 
 impl UserDeposit {
-    pub fn new(user_acct: String, token_amount: BigDecimal, mint_acct: String, tx_sig: String) -> Self {
+    pub fn new(
+        user_acct: String,
+        token_amount: BigDecimal,
+        mint_acct: String,
+        tx_sig: String,
+    ) -> Self {
         UserDeposit {
             user_acct,
             token_amount,
@@ -32,4 +37,4 @@ impl UserDeposit {
             created_at: Utc::now(),
         }
     }
-}
\ No newline at end of file
+}
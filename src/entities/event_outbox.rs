@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+
+table! {
+    event_outbox (outbox_id) {
+        outbox_id -> Int8,
+        channel -> Varchar,
+        payload -> Text,
+        created_at -> Timestamptz,
+        processed_at -> Nullable<Timestamptz>,
+        locked_until -> Nullable<Timestamptz>,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+    }
+}
+
+/**
+ * An event written by the db triggers alongside their NOTIFY
+ * (see migrations/2024-06-01-000300_create_event_outbox/up.sql).
+ * Rows stay pending until a handler succeeds, so nothing is lost while the watcher is down.
+ */
+#[derive(Queryable, QueryableByName, Clone, Debug)]
+#[diesel(table_name = event_outbox)]
+#[allow(dead_code)]
+pub struct OutboxEvent {
+    pub outbox_id: i64,
+    pub channel: String,
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
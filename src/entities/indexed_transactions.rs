@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+use std::fmt;
+
+table! {
+    indexed_transactions (tx_sig, indexer_version) {
+        tx_sig -> Varchar,
+        indexer_version -> Int2,
+        outcome -> Varchar,
+        error -> Nullable<Text>,
+        indexed_at -> Timestamptz,
+    }
+}
+
+/**
+ * Ledger entry for a transaction run through the indexers at a given indexer version.
+ * Transactions with an entry that did not fail are skipped unless indexing is forced.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = indexed_transactions)]
+pub struct IndexedTransaction {
+    pub tx_sig: String,
+    pub indexer_version: i16,
+    pub outcome: IndexOutcome,
+    /// the failing indexers and their errors, when any failed
+    pub error: Option<String>,
+    pub indexed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum IndexOutcome {
+    Indexed,
+    Failed,
+    /// no indexer exists for the tx's main instruction type
+    Unhandled,
+}
+
+impl fmt::Display for IndexOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexOutcome::Indexed => write!(f, "indexed"),
+            IndexOutcome::Failed => write!(f, "failed"),
+            IndexOutcome::Unhandled => write!(f, "unhandled"),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for IndexOutcome
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            IndexOutcome::Indexed => "indexed".to_sql(out),
+            IndexOutcome::Failed => "failed".to_sql(out),
+            IndexOutcome::Unhandled => "unhandled".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for IndexOutcome {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"indexed" => Ok(IndexOutcome::Indexed),
+            b"failed" => Ok(IndexOutcome::Failed),
+            b"unhandled" => Ok(IndexOutcome::Unhandled),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
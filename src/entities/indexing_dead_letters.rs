@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+table! {
+    indexing_dead_letters (tx_sig) {
+        tx_sig -> Varchar,
+        error_kind -> Varchar,
+        error -> Text,
+        attempts -> Int4,
+        first_failed_at -> Timestamptz,
+        last_failed_at -> Timestamptz,
+        next_retry_at -> Nullable<Timestamptz>,
+    }
+}
+
+/**
+ * A transaction whose indexing failed, kept until a retry succeeds.
+ * Once retries are exhausted or the error cannot succeed on retry, next_retry_at is None
+ * and the transaction stays stuck until someone looks at it.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Serialize, Clone, Debug)]
+// the upsert has to clear next_retry_at once a tx is given up on
+#[diesel(table_name = indexing_dead_letters, treat_none_as_null = true)]
+pub struct IndexingDeadLetter {
+    pub tx_sig: String,
+    /// kind of the first failing indexer's error, see AssetWatcherError::kind
+    pub error_kind: String,
+    pub error: String,
+    pub attempts: i32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    pub next_retry_at: Option<DateTime<Utc>>,
+}
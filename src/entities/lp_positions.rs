@@ -0,0 +1,113 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+
+table! {
+    lp_position_events (tx_sig, user_acct, amm_acct) {
+        tx_sig -> Varchar,
+        user_acct -> Varchar,
+        amm_acct -> Varchar,
+        lp_mint_acct -> Varchar,
+        kind -> Varchar,
+        base_amount -> Numeric,
+        quote_amount -> Numeric,
+        lp_amount -> Numeric,
+        slot -> Numeric,
+        block_time -> Timestamptz,
+    }
+}
+
+table! {
+    lp_positions (user_acct, amm_acct) {
+        user_acct -> Varchar,
+        amm_acct -> Varchar,
+        lp_mint_acct -> Varchar,
+        lp_tokens_minted -> Numeric,
+        lp_tokens_burned -> Numeric,
+        base_deposited -> Numeric,
+        quote_deposited -> Numeric,
+        base_withdrawn -> Numeric,
+        quote_withdrawn -> Numeric,
+        base_cost_basis -> Numeric,
+        quote_cost_basis -> Numeric,
+        updated_at -> Timestamptz,
+    }
+}
+
+/**
+ * One addLiquidity or removeLiquidity by a user on an AMM. Amounts are in token atoms and
+ * always positive: what went in for a deposit, what came out for a withdrawal.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = lp_position_events)]
+pub struct LpPositionEvent {
+    pub tx_sig: String,
+    pub user_acct: String,
+    pub amm_acct: String,
+    pub lp_mint_acct: String,
+    pub kind: LpEventKind,
+    pub base_amount: BigDecimal,
+    pub quote_amount: BigDecimal,
+    /// LP tokens minted for a deposit or burned for a withdrawal
+    pub lp_amount: BigDecimal,
+    pub slot: BigDecimal,
+    pub block_time: DateTime<Utc>,
+}
+
+/**
+ * A user's liquidity in an AMM, folded from their LP position events in slot order.
+ * The cost basis is what the LP tokens still held were bought with: deposits add to it and
+ * withdrawals take out the share of LP tokens burned, as an average cost.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = lp_positions)]
+pub struct LpPosition {
+    pub user_acct: String,
+    pub amm_acct: String,
+    pub lp_mint_acct: String,
+    pub lp_tokens_minted: BigDecimal,
+    pub lp_tokens_burned: BigDecimal,
+    pub base_deposited: BigDecimal,
+    pub quote_deposited: BigDecimal,
+    pub base_withdrawn: BigDecimal,
+    pub quote_withdrawn: BigDecimal,
+    pub base_cost_basis: BigDecimal,
+    pub quote_cost_basis: BigDecimal,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum LpEventKind {
+    Deposit,
+    Withdrawal,
+}
+
+impl<DB> ToSql<Text, DB> for LpEventKind
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            LpEventKind::Deposit => "deposit".to_sql(out),
+            LpEventKind::Withdrawal => "withdrawal".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for LpEventKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"deposit" => Ok(LpEventKind::Deposit),
+            b"withdrawal" => Ok(LpEventKind::Withdrawal),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
@@ -1,8 +1,18 @@
 pub mod auth;
+pub mod backfill_checkpoints;
 pub mod conditional_vaults;
+pub mod deposits;
+pub mod event_outbox;
+pub mod indexed_transactions;
+pub mod indexing_dead_letters;
+pub mod lp_positions;
 pub mod markets;
+pub mod orders;
+pub mod proposals;
+pub mod reconciliation_runs;
+pub mod swaps;
 pub mod token_acct_balances;
 pub mod token_accts;
 pub mod tokens;
 pub mod transactions;
-pub mod deposits;
+pub mod watch_targets;
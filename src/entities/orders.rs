@@ -0,0 +1,114 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+
+table! {
+    orders (order_tx_sig) {
+        order_tx_sig -> Varchar,
+        action -> Varchar,
+        market_acct -> Varchar,
+        actor_acct -> Varchar,
+        open_orders_acct -> Nullable<Varchar>,
+        side -> Nullable<Varchar>,
+        price_lots -> Nullable<Numeric>,
+        base_lots -> Nullable<Numeric>,
+        quote_lots -> Nullable<Numeric>,
+        client_order_id -> Nullable<Varchar>,
+        order_id -> Nullable<Varchar>,
+        order_type -> Nullable<Varchar>,
+        slot -> Numeric,
+        order_time -> Timestamptz,
+    }
+}
+
+/**
+ * An Openbook placeOrder or cancelOrder instruction, one per transaction.
+ * Side, price and size are only known for placed orders; a cancel only carries the order id.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = orders, treat_none_as_null = true)]
+pub struct Order {
+    pub order_tx_sig: String,
+    pub action: OrderAction,
+    pub market_acct: String,
+    /// the signer placing or cancelling the order
+    pub actor_acct: String,
+    pub open_orders_acct: Option<String>,
+    pub side: Option<OrderSide>,
+    pub price_lots: Option<BigDecimal>,
+    /// max base lots of a placed order
+    pub base_lots: Option<BigDecimal>,
+    /// max quote lots of a placed order, fees included
+    pub quote_lots: Option<BigDecimal>,
+    pub client_order_id: Option<String>,
+    pub order_id: Option<String>,
+    pub order_type: Option<String>,
+    pub slot: BigDecimal,
+    pub order_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum OrderAction {
+    Place,
+    Cancel,
+}
+
+impl<DB> ToSql<Text, DB> for OrderAction
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            OrderAction::Place => "place".to_sql(out),
+            OrderAction::Cancel => "cancel".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for OrderAction {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"place" => Ok(OrderAction::Place),
+            b"cancel" => Ok(OrderAction::Cancel),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum OrderSide {
+    Bid,
+    Ask,
+}
+
+impl<DB> ToSql<Text, DB> for OrderSide
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            OrderSide::Bid => "bid".to_sql(out),
+            OrderSide::Ask => "ask".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for OrderSide {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"bid" => Ok(OrderSide::Bid),
+            b"ask" => Ok(OrderSide::Ask),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+
+table! {
+    proposals (proposal_acct) {
+        proposal_acct -> Varchar,
+        dao_acct -> Varchar,
+        proposer_acct -> Varchar,
+        description_url -> Nullable<Varchar>,
+        base_pass_vault_acct -> Nullable<Varchar>,
+        base_fail_vault_acct -> Nullable<Varchar>,
+        quote_pass_vault_acct -> Nullable<Varchar>,
+        quote_fail_vault_acct -> Nullable<Varchar>,
+        pass_market_acct -> Nullable<Varchar>,
+        fail_market_acct -> Nullable<Varchar>,
+        status -> Varchar,
+        init_tx_sig -> Varchar,
+        init_slot -> Numeric,
+        initialized_at -> Timestamptz,
+        finalize_tx_sig -> Nullable<Varchar>,
+        finalized_at -> Nullable<Timestamptz>,
+    }
+}
+
+/**
+ * An Autocrat proposal with the conditional vaults and markets it trades on.
+ * Created Pending by initializeProposal; finalizeProposal settles it as Passed or Failed.
+ */
+#[derive(Queryable, Insertable, Clone, Debug)]
+#[diesel(table_name = proposals)]
+#[allow(dead_code)]
+pub struct Proposal {
+    pub proposal_acct: String,
+    pub dao_acct: String,
+    pub proposer_acct: String,
+    pub description_url: Option<String>,
+    pub base_pass_vault_acct: Option<String>,
+    pub base_fail_vault_acct: Option<String>,
+    pub quote_pass_vault_acct: Option<String>,
+    pub quote_fail_vault_acct: Option<String>,
+    pub pass_market_acct: Option<String>,
+    pub fail_market_acct: Option<String>,
+    pub status: ProposalStatus,
+    pub init_tx_sig: String,
+    pub init_slot: BigDecimal,
+    pub initialized_at: DateTime<Utc>,
+    pub finalize_tx_sig: Option<String>,
+    pub finalized_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ProposalStatus {
+    Pending,
+    Passed,
+    Failed,
+}
+
+impl<DB> ToSql<Text, DB> for ProposalStatus
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            ProposalStatus::Pending => "pending".to_sql(out),
+            ProposalStatus::Passed => "passed".to_sql(out),
+            ProposalStatus::Failed => "failed".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for ProposalStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"pending" => Ok(ProposalStatus::Pending),
+            b"passed" => Ok(ProposalStatus::Passed),
+            b"failed" => Ok(ProposalStatus::Failed),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+
+table! {
+    reconciliation_runs (run_id) {
+        run_id -> Int8,
+        started_at -> Timestamptz,
+        finished_at -> Timestamptz,
+        accts_checked -> Int4,
+        accts_corrected -> Int4,
+        accts_missing -> Int4,
+        accts_unreadable -> Int4,
+        failed_batches -> Int4,
+        corrected_accts -> Array<Text>,
+    }
+}
+
+/// Summary of one balance reconciliation pass over the Watching and Enabled token accts.
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = reconciliation_runs)]
+pub struct NewReconciliationRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub accts_checked: i32,
+    pub accts_corrected: i32,
+    /// accts that no longer exist on chain, e.g. closed; their balance is corrected to zero
+    pub accts_missing: i32,
+    /// accts that are not spl token accounts on chain, e.g. reassigned, and were skipped
+    pub accts_unreadable: i32,
+    pub failed_batches: i32,
+    pub corrected_accts: Vec<String>,
+}
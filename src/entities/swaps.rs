@@ -0,0 +1,77 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+
+table! {
+    swaps (swap_tx_sig) {
+        swap_tx_sig -> Varchar,
+        market_acct -> Varchar,
+        user_acct -> Varchar,
+        direction -> Varchar,
+        input_mint_acct -> Varchar,
+        output_mint_acct -> Varchar,
+        input_amount -> Numeric,
+        output_amount -> Numeric,
+        price -> Nullable<Numeric>,
+        slot -> Numeric,
+        block_time -> Timestamptz,
+    }
+}
+
+/**
+ * An AMM swap, one per transaction. Amounts are in token atoms of the input and output mints;
+ * the price is quote per base in token units, when the decimals of both are known.
+ */
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = swaps, treat_none_as_null = true)]
+pub struct Swap {
+    pub swap_tx_sig: String,
+    /// the amm traded against
+    pub market_acct: String,
+    pub user_acct: String,
+    pub direction: SwapDirection,
+    pub input_mint_acct: String,
+    pub output_mint_acct: String,
+    pub input_amount: BigDecimal,
+    pub output_amount: BigDecimal,
+    pub price: Option<BigDecimal>,
+    pub slot: BigDecimal,
+    pub block_time: DateTime<Utc>,
+}
+
+/// Buy spends quote for base, sell spends base for quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum SwapDirection {
+    Buy,
+    Sell,
+}
+
+impl<DB> ToSql<Text, DB> for SwapDirection
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            SwapDirection::Buy => "buy".to_sql(out),
+            SwapDirection::Sell => "sell".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for SwapDirection {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"buy" => Ok(SwapDirection::Buy),
+            b"sell" => Ok(SwapDirection::Sell),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use diesel::pg::Pg;
 use diesel::serialize::IsNull;
@@ -8,8 +9,8 @@ use diesel::{
     AsExpression,
 };
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io::Write;
-use bigdecimal::BigDecimal;
 
 table! {
     token_accts (token_acct) {
@@ -47,12 +48,12 @@ pub enum TokenAcctStatus {
     Disabled,
 }
 
-impl ToString for TokenAcctStatus {
-    fn to_string(&self) -> String {
+impl fmt::Display for TokenAcctStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenAcctStatus::Watching => "watching".to_string(),
-            TokenAcctStatus::Enabled => "enabled".to_string(),
-            TokenAcctStatus::Disabled => "disabled".to_string(),
+            TokenAcctStatus::Watching => write!(f, "watching"),
+            TokenAcctStatus::Enabled => write!(f, "enabled"),
+            TokenAcctStatus::Disabled => write!(f, "disabled"),
         }
     }
 }
@@ -84,14 +85,6 @@ pub struct TokenAcctsInsertChannelPayload {
     pub token_acct: String,
 }
 
-impl TokenAcctsInsertChannelPayload {
-    pub fn parse_payload(
-        json_str: &str,
-    ) -> Result<TokenAcctsInsertChannelPayload, serde_json::Error> {
-        serde_json::from_str(json_str)
-    }
-}
-
 // todo setup serialization
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenAcctsStatusUpdateChannelPayload {
@@ -99,14 +92,6 @@ pub struct TokenAcctsStatusUpdateChannelPayload {
     pub token_acct: String,
 }
 
-impl TokenAcctsStatusUpdateChannelPayload {
-    pub fn parse_payload(
-        json_str: &str,
-    ) -> Result<TokenAcctsStatusUpdateChannelPayload, serde_json::Error> {
-        serde_json::from_str(json_str)
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WatchTokenBalancePayload {
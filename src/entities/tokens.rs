@@ -14,6 +14,7 @@ table! {
 }
 
 #[derive(Queryable, Clone, Selectable)]
+#[allow(dead_code)]
 #[diesel(table_name = tokens)]
 pub struct Token {
     pub mint_acct: String,
@@ -1,3 +1,4 @@
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use diesel::pg::{Pg, PgValue};
 use diesel::{
@@ -7,7 +8,8 @@ use diesel::{
     sql_types::Text,
 };
 use serde::{Deserialize, Serialize};
-use bigdecimal::BigDecimal;
+use std::fmt;
+use std::str::FromStr;
 
 table! {
     transactions (tx_sig) {
@@ -23,6 +25,7 @@ table! {
 
 #[derive(Queryable, Clone, Selectable)]
 #[diesel(table_name = transactions)]
+#[allow(dead_code)]
 pub struct Transaction {
     pub tx_sig: String,
     pub slot: BigDecimal,
@@ -34,7 +37,7 @@ pub struct Transaction {
 }
 
 #[derive(Debug, Clone, Copy, AsExpression, FromSqlRow)]
-#[sql_type = "Text"]
+#[diesel(sql_type = Text)]
 pub enum InstructionType {
     VaultMintConditionalTokens,
     VaultMintAndAmmSwap,
@@ -50,60 +53,102 @@ pub enum InstructionType {
     VaultMintAndAMMSwap,
 }
 
+impl InstructionType {
+    /// Every type a stored name parses to; VaultMintAndAMMSwap shares its name with VaultMintAndAmmSwap.
+    const ALL: [InstructionType; 11] = [
+        InstructionType::VaultMintConditionalTokens,
+        InstructionType::VaultMintAndAmmSwap,
+        InstructionType::AmmSwap,
+        InstructionType::AmmDeposit,
+        InstructionType::AmmWithdraw,
+        InstructionType::OpenbookPlaceOrder,
+        InstructionType::OpenbookCancelOrder,
+        InstructionType::AutocratInitializeProposal,
+        InstructionType::AutocratFinalizeProposal,
+        InstructionType::VaultMergeConditionalTokens,
+        InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens,
+    ];
+
+    /// The name stored in transactions.main_ix_type and accepted in BACKFILL_IX_TYPES.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstructionType::VaultMintConditionalTokens => "vault_mint_conditional_tokens",
+            InstructionType::VaultMintAndAmmSwap | InstructionType::VaultMintAndAMMSwap => {
+                "vault_mint_and_amm_swap"
+            }
+            InstructionType::AmmSwap => "amm_swap",
+            InstructionType::AmmDeposit => "amm_deposit",
+            InstructionType::AmmWithdraw => "amm_withdraw",
+            InstructionType::OpenbookPlaceOrder => "openbook_place_order",
+            InstructionType::OpenbookCancelOrder => "openbook_cancel_order",
+            InstructionType::AutocratInitializeProposal => "autocrat_initialize_proposal",
+            InstructionType::AutocratFinalizeProposal => "autocrat_finalize_proposal",
+            InstructionType::VaultMergeConditionalTokens => "vault_merge_conditional_tokens",
+            InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens => {
+                "vault_redeem_conditional_tokens_for_underlying_tokens"
+            }
+        }
+    }
+
+    /**
+     * Version of the indexer for this instruction type, recorded in indexed_transactions.
+     * Bump it when an indexer changes so txs of that type are indexed again on the next backfill.
+     */
+    pub fn indexer_version(&self) -> i16 {
+        match self {
+            // 2: lp positions are tracked, and withdrawals found their instruction again
+            InstructionType::AmmDeposit | InstructionType::AmmWithdraw => 2,
+            // 2: were unhandled before the orders and proposals indexers
+            InstructionType::OpenbookPlaceOrder
+            | InstructionType::OpenbookCancelOrder
+            | InstructionType::AutocratInitializeProposal
+            | InstructionType::AutocratFinalizeProposal => 2,
+            // 2: swaps are recorded
+            InstructionType::AmmSwap | InstructionType::VaultMintAndAmmSwap => 2,
+            InstructionType::VaultMintConditionalTokens
+            | InstructionType::VaultMintAndAMMSwap
+            | InstructionType::VaultMergeConditionalTokens
+            | InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens => 1,
+        }
+    }
+}
+
+/// An instruction type name that matches no InstructionType.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownInstructionType(pub String);
+
+impl fmt::Display for UnknownInstructionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown instruction type: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownInstructionType {}
+
+impl FromStr for InstructionType {
+    type Err = UnknownInstructionType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InstructionType::ALL
+            .into_iter()
+            .find(|ix_type| ix_type.as_str() == s)
+            .ok_or_else(|| UnknownInstructionType(s.to_string()))
+    }
+}
+
 impl<DB> ToSql<Text, DB> for InstructionType
 where
     DB: Backend,
     str: ToSql<Text, DB>,
 {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
-        match self {
-            InstructionType::VaultMintConditionalTokens => {
-                "vault_mint_conditional_tokens".to_sql(out)
-            }
-            InstructionType::VaultMintAndAmmSwap => {
-                "vault_mint_and_amm_swap".to_sql(out)
-            }
-            InstructionType::AmmSwap => "amm_swap".to_sql(out),
-            InstructionType::AmmDeposit => "amm_deposit".to_sql(out),
-            InstructionType::AmmWithdraw => "amm_withdraw".to_sql(out),
-            InstructionType::OpenbookPlaceOrder => "openbook_place_order".to_sql(out),
-            InstructionType::OpenbookCancelOrder => "openbook_cancel_order".to_sql(out),
-            InstructionType::AutocratInitializeProposal => {
-                "autocrat_initialize_proposal".to_sql(out)
-            }
-            InstructionType::AutocratFinalizeProposal => "autocrat_finalize_proposal".to_sql(out),
-            InstructionType::VaultMergeConditionalTokens => {
-                "vault_merge_conditional_tokens".to_sql(out)
-            }
-            InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens => {
-                "vault_redeem_conditional_tokens_for_underlying_tokens".to_sql(out)
-            }
-            InstructionType::VaultMintAndAMMSwap => {
-              "vault_mint_and_amm_swap".to_sql(out)
-          }
-        }
+        self.as_str().to_sql(out)
     }
 }
 
 impl FromSql<Text, Pg> for InstructionType {
     fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
-        match bytes.as_bytes() {
-            b"vault_mint_conditional_tokens" => Ok(InstructionType::VaultMintConditionalTokens),
-            b"vault_mint_and_amm_swap" => Ok(InstructionType::VaultMintAndAmmSwap),
-            b"amm_swap" => Ok(InstructionType::AmmSwap),
-            b"amm_deposit" => Ok(InstructionType::AmmDeposit),
-            b"amm_withdraw" => Ok(InstructionType::AmmWithdraw),
-            b"openbook_place_order" => Ok(InstructionType::OpenbookPlaceOrder),
-            b"openbook_cancel_order" => Ok(InstructionType::OpenbookCancelOrder),
-            b"autocrat_initialize_proposal" => Ok(InstructionType::AutocratInitializeProposal),
-            b"autocrat_finalize_proposal" => Ok(InstructionType::AutocratFinalizeProposal),
-            b"vault_merge_conditional_tokens" => Ok(InstructionType::VaultMergeConditionalTokens),
-            b"vault_mint_and_amm_swap" => Ok(InstructionType::VaultMergeConditionalTokens),
-            b"vault_redeem_conditional_tokens_for_underlying_tokens" => {
-                Ok(InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens)
-            }
-            x => Err(format!("Unrecognized variant {:?}", x).into()),
-        }
+        Ok(std::str::from_utf8(bytes.as_bytes())?.parse()?)
     }
 }
 
@@ -123,7 +168,7 @@ pub struct Payload {
 
 impl Payload {
     pub fn parse_payload(json_str: &str) -> Result<Payload, serde_json::Error> {
-      serde_json::from_str(json_str)
+        serde_json::from_str(json_str)
     }
     pub fn get_main_ix_type(&self) -> Option<InstructionType> {
         for ix in &self.instructions {
@@ -207,10 +252,6 @@ pub struct TransactionsInsertChannelPayload {
     pub tx_sig: String,
 }
 
-impl TransactionsInsertChannelPayload {
-    pub fn parse_payload(
-        json_str: &str,
-    ) -> Result<TransactionsInsertChannelPayload, serde_json::Error> {
-        serde_json::from_str(json_str)
-    }
-}
+#[cfg(test)]
+#[path = "transactions_test.rs"]
+mod tests;
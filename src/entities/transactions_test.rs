@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_instruction_type_names_round_trip() {
+    for ix_type in InstructionType::ALL {
+        let parsed: InstructionType = ix_type.as_str().parse().expect("name should parse");
+        assert_eq!(parsed.as_str(), ix_type.as_str());
+    }
+}
+
+#[test]
+fn test_legacy_instruction_type_reads_back_as_current_one() {
+    let parsed: InstructionType = InstructionType::VaultMintAndAMMSwap
+        .as_str()
+        .parse()
+        .expect("name should parse");
+    assert!(matches!(parsed, InstructionType::VaultMintAndAmmSwap));
+}
+
+#[test]
+fn test_unknown_instruction_type_is_an_error() {
+    let err = "amm_swapp"
+        .parse::<InstructionType>()
+        .expect_err("name should not parse");
+    assert_eq!(err, UnknownInstructionType("amm_swapp".to_string()));
+    assert_eq!(err.to_string(), "unknown instruction type: amm_swapp");
+}
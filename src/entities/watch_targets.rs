@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use diesel::pg::{Pg, PgValue};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::entities::token_accts::TokenAcctStatus;
+
+table! {
+    watch_targets (target_acct, target_type) {
+        target_acct -> Varchar,
+        target_type -> Varchar,
+        status -> crate::entities::token_accts::TokenAcctStatusType,
+        created_at -> Timestamptz,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+/**
+ * An owner wallet or a mint whose token accounts are tracked through one program subscription,
+ * rather than an account subscription per token acct.
+ */
+#[derive(Queryable, Clone, Insertable, Selectable)]
+#[diesel(table_name = watch_targets)]
+pub struct WatchTarget {
+    pub target_acct: String,
+    pub target_type: WatchTargetType,
+    pub status: TokenAcctStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum WatchTargetType {
+    Owner,
+    Mint,
+}
+
+impl WatchTargetType {
+    /// Offset of the field in an spl token account that the program subscription filters on.
+    pub fn memcmp_offset(&self) -> usize {
+        match self {
+            WatchTargetType::Mint => 0,
+            WatchTargetType::Owner => 32,
+        }
+    }
+}
+
+impl fmt::Display for WatchTargetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTargetType::Owner => write!(f, "owner"),
+            WatchTargetType::Mint => write!(f, "mint"),
+        }
+    }
+}
+
+impl<DB> ToSql<Text, DB> for WatchTargetType
+where
+    DB: Backend,
+    str: ToSql<Text, DB>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+        match self {
+            WatchTargetType::Owner => "owner".to_sql(out),
+            WatchTargetType::Mint => "mint".to_sql(out),
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for WatchTargetType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"owner" => Ok(WatchTargetType::Owner),
+            b"mint" => Ok(WatchTargetType::Mint),
+            x => Err(format!("Unrecognized variant {:?}", x).into()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchOwnerPayload {
+    pub owner_acct: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchMintPayload {
+    pub mint_acct: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MintHolder {
+    pub token_acct: String,
+    pub owner_acct: String,
+    pub amount: String,
+}
@@ -0,0 +1,46 @@
+use std::future::Future;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Config;
+use crate::errors::AssetWatcherError;
+use crate::shutdown::Shutdown;
+
+/// Retries with exponential backoff until it succeeds. Returns None if shutdown comes first.
+pub async fn with_backoff<T, F, Fut>(
+    config: &Config,
+    shutdown: &Shutdown,
+    label: &str,
+    attempt: F,
+) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AssetWatcherError>>,
+{
+    with_backoff_until(config, &shutdown.child_token(), label, attempt).await
+}
+
+/// Same as `with_backoff`, but gives up when `cancel` is cancelled, e.g. a single subscription's token.
+pub async fn with_backoff_until<T, F, Fut>(
+    config: &Config,
+    cancel: &CancellationToken,
+    label: &str,
+    mut attempt: F,
+) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AssetWatcherError>>,
+{
+    let mut backoff = config.reconnect_backoff_initial;
+    loop {
+        match attempt().await {
+            Ok(value) => return Some(value),
+            Err(e) => eprintln!("{} failed, retrying in {:?}: {}", label, backoff, e),
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return None,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(config.reconnect_backoff_max);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod backoff;
+pub mod notification_handler;
+pub mod outbox;
+pub mod pubsub_supervisor;
+pub mod rpc_program_updates;
 pub mod rpc_token_acct_updates;
 pub mod setup;
+pub mod subscriptions;
 pub mod token_accts_insert;
 pub mod token_accts_status_update;
 pub mod transactions_insert;
+pub mod worker_pool;
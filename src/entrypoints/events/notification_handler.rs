@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+use crate::entrypoints::events::worker_pool::WorkerPool;
+use crate::errors::AssetWatcherError;
+
+/**
+ * Handles the events raised on one postgres channel.
+ * The raw payload is parsed into `Payload` before `handle` is called, so a handler only deals
+ * with the typed payload and can be driven directly with a synthetic one.
+ */
+#[async_trait]
+pub trait NotificationHandler: Send + Sync + 'static {
+    type Payload: DeserializeOwned + Send;
+
+    fn channel(&self) -> &'static str;
+
+    async fn handle(&self, payload: Self::Payload) -> Result<(), AssetWatcherError>;
+}
+
+/// Parses the raw payload for a handler, so handlers with different payloads can share a registry.
+#[async_trait]
+trait Dispatch: Send + Sync {
+    async fn dispatch(&self, payload: &str) -> Result<(), AssetWatcherError>;
+}
+
+#[async_trait]
+impl<H: NotificationHandler> Dispatch for H {
+    async fn dispatch(&self, payload: &str) -> Result<(), AssetWatcherError> {
+        let parsed = serde_json::from_str::<H::Payload>(payload)?;
+        self.handle(parsed).await
+    }
+}
+
+#[derive(Clone)]
+struct Registered {
+    handler: Arc<dyn Dispatch>,
+    workers: WorkerPool,
+}
+
+/**
+ * Every channel we LISTEN on and consume from the outbox, with its handler and the worker pool
+ * its events run on. Adding a channel only takes registering a handler here, plus a trigger
+ * that enqueues its events.
+ */
+#[derive(Clone, Default)]
+pub struct NotificationRegistry {
+    // shared, since the registry is cloned into every outbox job
+    handlers: Arc<HashMap<&'static str, Registered>>,
+}
+
+impl NotificationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<H: NotificationHandler>(mut self, handler: H, workers: WorkerPool) -> Self {
+        let channel = handler.channel();
+        if self.handlers.contains_key(channel) {
+            eprintln!("replacing handler already registered for {}", channel);
+        }
+        Arc::make_mut(&mut self.handlers).insert(
+            channel,
+            Registered {
+                handler: Arc::new(handler),
+                workers,
+            },
+        );
+        self
+    }
+
+    pub fn channels(&self) -> Vec<&'static str> {
+        let mut channels: Vec<&'static str> = self.handlers.keys().copied().collect();
+        channels.sort();
+        channels
+    }
+
+    pub fn handles(&self, channel: &str) -> bool {
+        self.handlers.contains_key(channel)
+    }
+
+    /// The pool events on the channel should run on, if anything handles it.
+    pub fn workers_for(&self, channel: &str) -> Option<&WorkerPool> {
+        self.handlers
+            .get(channel)
+            .map(|registered| &registered.workers)
+    }
+
+    pub async fn dispatch(&self, channel: &str, payload: &str) -> Result<(), AssetWatcherError> {
+        let registered = self.handlers.get(channel).ok_or_else(|| {
+            AssetWatcherError::PayloadParse(format!("no handler for channel: {}", channel))
+        })?;
+        println!("new {} payload: {:?}", channel, payload);
+        let res = registered.handler.dispatch(payload).await;
+        match &res {
+            Ok(()) => println!("successfully handled {} notification", channel),
+            Err(e) => eprintln!("error handling {} notification: {:?}", channel, e),
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+#[path = "notification_handler_test.rs"]
+mod tests;
@@ -0,0 +1,129 @@
+use super::*;
+use crate::config::OverflowPolicy;
+use crate::entities::transactions::TransactionsInsertChannelPayload;
+use crate::shutdown::Shutdown;
+use std::sync::Mutex;
+
+struct RecordingHandler {
+    channel: &'static str,
+    handled: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl NotificationHandler for RecordingHandler {
+    type Payload = TransactionsInsertChannelPayload;
+
+    fn channel(&self) -> &'static str {
+        self.channel
+    }
+
+    async fn handle(
+        &self,
+        payload: TransactionsInsertChannelPayload,
+    ) -> Result<(), AssetWatcherError> {
+        if payload.tx_sig == "fail" {
+            return Err(AssetWatcherError::MissingTransaction(payload.tx_sig));
+        }
+        self.handled.lock().unwrap().push(payload.tx_sig);
+        Ok(())
+    }
+}
+
+fn workers(name: &'static str, shutdown: &Shutdown) -> WorkerPool {
+    WorkerPool::new(name, 1, 1, OverflowPolicy::Block, shutdown)
+}
+
+fn registry_with(
+    channels: &[&'static str],
+    shutdown: &Shutdown,
+) -> (NotificationRegistry, Arc<Mutex<Vec<String>>>) {
+    let handled = Arc::new(Mutex::new(vec![]));
+    let registry = channels
+        .iter()
+        .fold(NotificationRegistry::new(), |registry, channel| {
+            registry.register(
+                RecordingHandler {
+                    channel,
+                    handled: Arc::clone(&handled),
+                },
+                workers(channel, shutdown),
+            )
+        });
+    (registry, handled)
+}
+
+#[tokio::test]
+async fn test_registry_lists_registered_channels() {
+    let shutdown = Shutdown::new();
+    let (registry, _) = registry_with(&["b_channel", "a_channel"], &shutdown);
+
+    assert_eq!(registry.channels(), vec!["a_channel", "b_channel"]);
+    assert!(registry.handles("a_channel"));
+    assert!(!registry.handles("c_channel"));
+    assert!(registry.workers_for("b_channel").is_some());
+    assert!(registry.workers_for("c_channel").is_none());
+}
+
+#[tokio::test]
+async fn test_registering_a_channel_again_replaces_its_handler() {
+    let shutdown = Shutdown::new();
+    let (registry, first_handled) = registry_with(&["a_channel"], &shutdown);
+    let second_handled = Arc::new(Mutex::new(vec![]));
+    let registry = registry.register(
+        RecordingHandler {
+            channel: "a_channel",
+            handled: Arc::clone(&second_handled),
+        },
+        workers("a_channel", &shutdown),
+    );
+
+    registry
+        .dispatch("a_channel", r#"{"tx_sig":"sig"}"#)
+        .await
+        .expect("dispatch should succeed");
+
+    assert_eq!(registry.channels(), vec!["a_channel"]);
+    assert!(first_handled.lock().unwrap().is_empty());
+    assert_eq!(*second_handled.lock().unwrap(), vec!["sig"]);
+}
+
+#[tokio::test]
+async fn test_dispatch_parses_the_payload_for_the_handler() {
+    let shutdown = Shutdown::new();
+    let (registry, handled) = registry_with(&["a_channel"], &shutdown);
+
+    registry
+        .dispatch("a_channel", r#"{"tx_sig":"sig"}"#)
+        .await
+        .expect("dispatch should succeed");
+
+    assert_eq!(*handled.lock().unwrap(), vec!["sig"]);
+}
+
+#[tokio::test]
+async fn test_dispatch_errors() {
+    let shutdown = Shutdown::new();
+    let (registry, handled) = registry_with(&["a_channel"], &shutdown);
+
+    let unknown_channel = registry.dispatch("c_channel", r#"{"tx_sig":"sig"}"#).await;
+    assert!(matches!(
+        unknown_channel,
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+
+    let bad_payload = registry
+        .dispatch("a_channel", r#"{"token_acct":"acct"}"#)
+        .await;
+    assert!(matches!(
+        bad_payload,
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+
+    let handler_error = registry.dispatch("a_channel", r#"{"tx_sig":"fail"}"#).await;
+    assert!(matches!(
+        handler_error,
+        Err(AssetWatcherError::MissingTransaction(_))
+    ));
+
+    assert!(handled.lock().unwrap().is_empty());
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double};
+use tokio::sync::Notify;
+
+use crate::config::Config;
+use crate::entities::event_outbox::{event_outbox, OutboxEvent};
+use crate::entrypoints::events::notification_handler::NotificationRegistry;
+use crate::errors::AssetWatcherError;
+use crate::shutdown::Shutdown;
+
+const CLAIM_BATCH_SIZE: i64 = 50;
+// a claimed event whose worker died becomes visible again after this long, counted from when it
+// was claimed and again from when a worker picked it up
+const CLAIM_LEASE: Duration = Duration::from_secs(300);
+// how long a failed event waits before it is claimed again, doubling with every attempt
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const RETRY_DELAY_MAX: Duration = Duration::from_secs(60 * 60);
+// an event still failing after this many claims is given up on and left with its last error
+const MAX_ATTEMPTS: i32 = 10;
+
+/**
+ * Works through pending outbox events until none are left, then waits to be woken by a
+ * NOTIFY hint or the poll interval. Events are claimed with FOR UPDATE SKIP LOCKED so several
+ * watchers can share the queue, and only marked processed once their handler succeeds.
+ * Claimed events are handed to the worker pool for their kind, and claiming waits while that
+ * pool's queue is full. An event can sit in the queue past its lease and be claimed again, so a
+ * worker first renews the claim, and skips the event if another claim took it over meanwhile.
+ */
+pub async fn run_consumer(
+    config: Arc<Config>,
+    pool: Pool,
+    registry: NotificationRegistry,
+    wake: Arc<Notify>,
+    shutdown: Shutdown,
+) {
+    loop {
+        if let Err(e) = drain(&pool, &registry, &shutdown).await {
+            eprintln!("error draining event outbox: {}", e);
+        }
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                println!("shutdown requested, no longer consuming the event outbox");
+                return;
+            }
+            _ = wake.notified() => {}
+            _ = tokio::time::sleep(config.outbox_poll_interval) => {}
+        }
+    }
+}
+
+async fn drain(
+    pool: &Pool,
+    registry: &NotificationRegistry,
+    shutdown: &Shutdown,
+) -> Result<(), AssetWatcherError> {
+    while !shutdown.is_triggered() {
+        let events = claim_batch(pool.clone()).await?;
+        if events.is_empty() {
+            break;
+        }
+        for event in events {
+            let channel = event.channel.clone();
+            let job = process_event(pool.clone(), registry.clone(), event);
+            match registry.workers_for(&channel) {
+                Some(workers) => {
+                    workers.submit(job).await;
+                }
+                // nothing handles the channel, so this only records the error on the event
+                None => job.await,
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn process_event(pool: Pool, registry: NotificationRegistry, event: OutboxEvent) {
+    let outbox_id = event.outbox_id;
+    let attempts = match start_attempt(pool.clone(), &event).await {
+        Ok(Some(attempts)) => attempts,
+        Ok(None) => {
+            println!(
+                "outbox event {} was claimed again while queued, skipping",
+                outbox_id
+            );
+            return;
+        }
+        Err(e) => {
+            eprintln!("error starting outbox event {}: {}", outbox_id, e);
+            return;
+        }
+    };
+
+    let res = registry.dispatch(&event.channel, &event.payload).await;
+
+    let settle_res = match res {
+        Ok(()) => ack(pool, outbox_id, None).await,
+        Err(e) if e.is_retryable() && attempts < MAX_ATTEMPTS => {
+            release_for_retry(pool, outbox_id, attempts, e.to_string()).await
+        }
+        // retrying cannot fix these, or has not so far, so record the error and stop retrying
+        Err(e) => {
+            eprintln!(
+                "giving up on outbox event {} after {} attempts: {}",
+                outbox_id, attempts, e
+            );
+            ack(pool, outbox_id, Some(e.to_string())).await
+        }
+    };
+    if let Err(e) = settle_res {
+        eprintln!("error settling outbox event {}: {}", outbox_id, e);
+    }
+}
+
+async fn claim_batch(pool: Pool) -> Result<Vec<OutboxEvent>, AssetWatcherError> {
+    let events = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            diesel::sql_query(
+                "UPDATE event_outbox
+                SET locked_until = now() + make_interval(secs => $1)
+                WHERE outbox_id IN (
+                    SELECT outbox_id FROM event_outbox
+                    WHERE processed_at IS NULL AND (locked_until IS NULL OR locked_until < now())
+                    ORDER BY outbox_id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *",
+            )
+            .bind::<Double, _>(CLAIM_LEASE.as_secs_f64())
+            .bind::<BigInt, _>(CLAIM_BATCH_SIZE)
+            .load::<OutboxEvent>(conn)
+        })
+        .await??;
+    Ok(events)
+}
+
+/**
+ * Renews the lease from now and counts the attempt, as long as the event is still held by the
+ * claim it was queued under. Returns the attempt number, or None if the claim was lost.
+ */
+async fn start_attempt(pool: Pool, event: &OutboxEvent) -> Result<Option<i32>, AssetWatcherError> {
+    let outbox_id = event.outbox_id;
+    let claimed_until = event.locked_until;
+    let lease_until = Utc::now() + chrono::Duration::from_std(CLAIM_LEASE).unwrap_or_default();
+    let attempts = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            diesel::update(
+                event_outbox::table
+                    .filter(event_outbox::outbox_id.eq(outbox_id))
+                    .filter(event_outbox::processed_at.is_null())
+                    .filter(event_outbox::locked_until.eq(claimed_until)),
+            )
+            .set((
+                event_outbox::locked_until.eq(lease_until),
+                event_outbox::attempts.eq(event_outbox::attempts + 1),
+            ))
+            .returning(event_outbox::attempts)
+            .get_result::<i32>(conn)
+            .optional()
+        })
+        .await??;
+    Ok(attempts)
+}
+
+async fn ack(pool: Pool, outbox_id: i64, error: Option<String>) -> Result<(), AssetWatcherError> {
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            diesel::update(event_outbox::table.filter(event_outbox::outbox_id.eq(outbox_id)))
+                .set((
+                    event_outbox::processed_at.eq(Utc::now()),
+                    event_outbox::locked_until.eq(None::<chrono::DateTime<Utc>>),
+                    event_outbox::last_error.eq(error),
+                ))
+                .execute(conn)
+        })
+        .await??;
+    Ok(())
+}
+
+async fn release_for_retry(
+    pool: Pool,
+    outbox_id: i64,
+    attempts: i32,
+    error: String,
+) -> Result<(), AssetWatcherError> {
+    let retry_at =
+        Utc::now() + chrono::Duration::from_std(retry_delay(attempts)).unwrap_or_default();
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            diesel::update(event_outbox::table.filter(event_outbox::outbox_id.eq(outbox_id)))
+                .set((
+                    event_outbox::locked_until.eq(retry_at),
+                    event_outbox::last_error.eq(error),
+                ))
+                .execute(conn)
+        })
+        .await??;
+    Ok(())
+}
+
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 16) as u32;
+    (RETRY_DELAY * 2u32.pow(exponent)).min(RETRY_DELAY_MAX)
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+
+use crate::adapters;
+use crate::config::Config;
+use crate::entrypoints::events::backoff::with_backoff;
+use crate::entrypoints::events::setup::{load_watching_targets, load_watching_token_accts};
+use crate::entrypoints::events::subscriptions::SubscriptionManager;
+use crate::shutdown::Shutdown;
+
+/**
+ * Rebuilds the pubsub client whenever the websocket drops and resubscribes every account and
+ * watch target still in Watching status. Each outage window is logged so the gap can be backfilled.
+ */
+pub async fn run(
+    config: Arc<Config>,
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+    shutdown: Shutdown,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = subscriptions.wait_for_disconnect() => {}
+        }
+        let disconnected_at = Utc::now();
+        eprintln!(
+            "websocket connection dropped at {}, reconnecting",
+            disconnected_at
+        );
+
+        let pub_sub_client = match with_backoff(&config, &shutdown, "websocket reconnect", || {
+            adapters::rpc::get_pubsub_client(&config.rpc_endpoint_wss)
+        })
+        .await
+        {
+            Some(pub_sub_client) => pub_sub_client,
+            None => return,
+        };
+        let token_accts_vec = match with_backoff(&config, &shutdown, "load watching accts", || {
+            load_watching_token_accts(&pool)
+        })
+        .await
+        {
+            Some(token_accts_vec) => token_accts_vec,
+            None => return,
+        };
+
+        let targets = match with_backoff(&config, &shutdown, "load watching targets", || {
+            load_watching_targets(&pool)
+        })
+        .await
+        {
+            Some(targets) => targets,
+            None => return,
+        };
+
+        let resubscribed = token_accts_vec.len() + targets.len();
+        subscriptions.replace_client(pub_sub_client, token_accts_vec, targets);
+        let reconnected_at = Utc::now();
+        println!(
+            "websocket reconnected, outage window {} to {} ({}s), resubscribed {} token accts and watch targets",
+            disconnected_at,
+            reconnected_at,
+            (reconnected_at - disconnected_at).num_seconds(),
+            resubscribed
+        );
+    }
+}
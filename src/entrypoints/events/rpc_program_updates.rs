@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use deadpool_diesel::postgres::Pool;
+use futures::StreamExt;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{OptionalContext, RpcKeyedAccount};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Config;
+use crate::entities::watch_targets::WatchTargetType;
+use crate::entrypoints::events::backoff::with_backoff_until;
+use crate::entrypoints::events::subscriptions::SubscriberExit;
+use crate::entrypoints::events::worker_pool::WorkerPool;
+use crate::errors::AssetWatcherError;
+use crate::services::balances;
+
+/**
+ * Subscribes to every spl token account matching the watch target (an owner wallet or a mint),
+ * so its token accts are tracked without a subscription of their own, including ones created later.
+ * A closed token acct no longer matches the data size filter and sends no notification, so the
+ * subscription never sees it go; balance reconciliation corrects its balance to zero.
+ */
+pub async fn new_handler(
+    config: Arc<Config>,
+    pub_sub_client: Arc<PubsubClient>,
+    pool: Pool,
+    target_type: WatchTargetType,
+    target_pubkey: Pubkey,
+    account_updates: WorkerPool,
+    cancel: CancellationToken,
+) -> SubscriberExit {
+    if cancel.is_cancelled() {
+        return SubscriberExit::Cancelled;
+    }
+
+    // a failed subscribe says nothing about the websocket, so only this target is retried
+    let client = &pub_sub_client;
+    let filter_config = &config;
+    let program_subscribe_res = with_backoff_until(
+        &config,
+        &cancel,
+        &format!(
+            "subscribing to token program for {:?} {}",
+            target_type, target_pubkey
+        ),
+        || async move {
+            client
+                .program_subscribe(
+                    &spl_token::id(),
+                    Some(token_accts_filter(
+                        filter_config,
+                        target_type,
+                        &target_pubkey,
+                    )),
+                )
+                .await
+                .map_err(AssetWatcherError::from)
+        },
+    )
+    .await;
+
+    let Some((mut subscription, unsubscribe)) = program_subscribe_res else {
+        return SubscriberExit::Cancelled;
+    };
+
+    println!(
+        "successfully subscribed to token accts for {:?}: {}",
+        target_type, target_pubkey
+    );
+
+    // seed after subscribing so nothing that changes in between is missed
+    let seeding = seed_token_accts(&config, pool.clone(), target_type, target_pubkey, &cancel);
+    tokio::pin!(seeding);
+    let mut seeding_done = false;
+
+    loop {
+        let val = tokio::select! {
+            _ = cancel.cancelled() => {
+                unsubscribe().await;
+                println!("unsubscribed from token accts for {:?}: {}", target_type, target_pubkey);
+                return SubscriberExit::Cancelled;
+            }
+            res = &mut seeding, if !seeding_done => {
+                seeding_done = true;
+                match res {
+                    Ok(seeded) => println!(
+                        "seeded {} token accts for {:?}: {}",
+                        seeded, target_type, target_pubkey
+                    ),
+                    Err(e) => eprintln!(
+                        "error seeding token accts for {:?} {}: {}",
+                        target_type, target_pubkey, e
+                    ),
+                }
+                continue;
+            }
+            val = subscription.next() => match val {
+                Some(val) => val,
+                None => break,
+            },
+        };
+        let slot = val.context.slot;
+        let keyed_account = val.value;
+        let pool_clone = pool.clone();
+        let dropped_token_acct = keyed_account.pubkey.clone();
+        let queued = account_updates
+            .submit(async move {
+                let token_acct = keyed_account.pubkey.clone();
+                let res = match decode_token_account(&keyed_account.account) {
+                    Ok(token_account) => {
+                        balances::handle_program_token_acct(
+                            pool_clone,
+                            token_acct.clone(),
+                            token_account,
+                            slot,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                match res {
+                    Ok(()) => println!("successfully handled token acct update: {}", token_acct),
+                    Err(e) => eprintln!("error handling token acct update {}: {}", token_acct, e),
+                }
+            })
+            .await;
+        if !queued {
+            eprintln!(
+                "dropped update for token acct {} at slot {}",
+                dropped_token_acct, slot
+            );
+        }
+    }
+    println!(
+        "end of rpc program subscriber scope for {:?}: {}",
+        target_type, target_pubkey
+    );
+    SubscriberExit::StreamEnded
+}
+
+/**
+ * Loads every token acct currently matching the watch target with getProgramAccounts, so holders
+ * that do not change after we subscribe are still recorded. The accts are recorded at the slot
+ * of the response's context, which is the slot their data is from. Returns how many were seen.
+ */
+async fn seed_token_accts(
+    config: &Config,
+    pool: Pool,
+    target_type: WatchTargetType,
+    target_pubkey: Pubkey,
+    cancel: &CancellationToken,
+) -> Result<usize, AssetWatcherError> {
+    let rpc_client =
+        RpcClient::new_with_commitment(config.rpc_endpoint_http.clone(), config.commitment);
+    let program_accounts_config = token_accts_filter(config, target_type, &target_pubkey);
+    // get_program_accounts_with_config drops the context, so the request is sent as is
+    let response = rpc_client
+        .send::<OptionalContext<Vec<RpcKeyedAccount>>>(
+            RpcRequest::GetProgramAccounts,
+            serde_json::json!([spl_token::id().to_string(), program_accounts_config]),
+        )
+        .await?;
+    let (slot, token_accts) = match response {
+        OptionalContext::Context(response) => (response.context.slot, response.value),
+        OptionalContext::NoContext(_) => {
+            return Err(AssetWatcherError::PayloadParse(
+                "getProgramAccounts response has no context slot".to_string(),
+            ))
+        }
+    };
+
+    let seen = token_accts.len();
+    for keyed_account in token_accts {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let res = match decode_token_account(&keyed_account.account) {
+            Ok(token_account) => {
+                balances::handle_program_token_acct(
+                    pool.clone(),
+                    keyed_account.pubkey.clone(),
+                    token_account,
+                    slot,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            eprintln!("error seeding token acct {}: {}", keyed_account.pubkey, e);
+        }
+    }
+    Ok(seen)
+}
+
+/// Matches spl token accounts (165 bytes) whose field at the target's offset is the target pubkey.
+pub fn token_accts_filter(
+    config: &Config,
+    target_type: WatchTargetType,
+    target_pubkey: &Pubkey,
+) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(spl_token::state::Account::LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                target_type.memcmp_offset(),
+                target_pubkey.as_ref(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: Some(config.commitment),
+            min_context_slot: None,
+        },
+        with_context: Some(true),
+    }
+}
+
+fn decode_token_account(
+    ui_account: &solana_account_decoder::UiAccount,
+) -> Result<spl_token::state::Account, AssetWatcherError> {
+    let account: solana_sdk::account::Account = ui_account.decode().ok_or_else(|| {
+        AssetWatcherError::PayloadParse("could not decode token account data".to_string())
+    })?;
+    Ok(spl_token::state::Account::unpack(&account.data)?)
+}
@@ -1,140 +1,259 @@
-use std::env;
 use std::sync::Arc;
 
-use chrono::Utc;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::{update, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use deadpool_diesel::postgres::Pool;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
 use futures::StreamExt;
 use solana_account_decoder::{UiAccount, UiAccountData};
 use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
-use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
-use std::sync::Mutex;
-use tokio::task;
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::Instant;
 
 use crate::adapters;
-use crate::entities::token_accts::token_accts::{self};
-use crate::entities::token_accts::{TokenAcct, TokenAcctStatus};
+use crate::config::Config;
+use crate::entities::token_accts::{token_accts, TokenAcct};
 use crate::entities::transactions::transactions::{self, tx_sig};
 use crate::entities::transactions::Transaction;
-use crate::services::balances;
+use crate::entrypoints::events::backoff::with_backoff_until;
+use crate::entrypoints::events::subscriptions::SubscriberExit;
+use crate::entrypoints::events::worker_pool::WorkerPool;
+use crate::errors::AssetWatcherError;
 use crate::services::transactions::handle_token_acct_balance_tx;
+use crate::services::{balance_history, balances};
 use diesel::OptionalExtension;
+use tokio_util::sync::CancellationToken;
 
 use bigdecimal::BigDecimal;
 
+// a quiet subscription whose balance cannot be checked this many times in a row is renewed
+const MAX_FAILED_STALENESS_CHECKS: u32 = 3;
+
 pub async fn new_handler(
+    config: Arc<Config>,
     pub_sub_client: Arc<PubsubClient>,
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     token_acct_pubkey: Pubkey,
     token_acct_record: TokenAcct,
-) {
-    
-    let rpc_endpoint = env::var("RPC_ENDPOINT_HTTP").expect("RPC_ENDPOINT_HTTP must be set");
-    if let Err(e) = check_and_update_initial_balance(
-        rpc_endpoint,
-        Arc::clone(&conn_manager),
-        &token_acct_pubkey,
-        &token_acct_record,
-    )
-    .await
-    {
-      eprintln!("Error during initial balance check: {:?}", e);
+    account_updates: WorkerPool,
+    cancel: CancellationToken,
+) -> SubscriberExit {
+    if cancel.is_cancelled() {
+        return SubscriberExit::Cancelled;
     }
+    'subscription: loop {
+        if let Err(e) =
+            check_and_update_initial_balance(&config, pool.clone(), &token_acct_pubkey).await
+        {
+            eprintln!("Error during initial balance check: {:?}", e);
+        }
 
-    let timeout_flag = Arc::new(Mutex::new(true));
-
-    let account_subscribe_res = pub_sub_client
-        .account_subscribe(
-            &token_acct_pubkey,
-            Some(RpcAccountInfoConfig {
-                encoding: Some(solana_account_decoder::UiAccountEncoding::JsonParsed),
-                data_slice: None,
-                commitment: Some(CommitmentConfig::confirmed()),
-                min_context_slot: None,
-            }),
+        // a failed subscribe says nothing about the websocket, so only this account is retried
+        let client = &pub_sub_client;
+        let commitment = config.commitment;
+        let account_subscribe_res = with_backoff_until(
+            &config,
+            &cancel,
+            &format!("subscribing to token acct {}", token_acct_pubkey),
+            || async move {
+                client
+                    .account_subscribe(
+                        &token_acct_pubkey,
+                        Some(RpcAccountInfoConfig {
+                            encoding: Some(solana_account_decoder::UiAccountEncoding::JsonParsed),
+                            data_slice: None,
+                            commitment: Some(commitment),
+                            min_context_slot: None,
+                        }),
+                    )
+                    .await
+                    .map_err(AssetWatcherError::from)
+            },
         )
         .await;
 
-    if account_subscribe_res.is_err() {
-        eprintln!(
-            "error when subscribing to account, {:?}",
-            account_subscribe_res.err().unwrap()
-        );
-        return;
-    }
+        let Some((mut subscription, unsubscribe)) = account_subscribe_res else {
+            return SubscriberExit::Cancelled;
+        };
 
-    println!(
-        "successfully subscribed to token acct: {}",
-        token_acct_pubkey.to_string()
-    );
+        println!(
+            "successfully subscribed to token acct: {}",
+            token_acct_pubkey
+        );
 
-    let (mut subscription, _) = account_subscribe_res.ok().unwrap();
-
-    let conn_manager_clone_sub = Arc::clone(&conn_manager);
-    while let Some(val) = subscription.next().await {
-        let mut timeout_flag_val = timeout_flag.lock().unwrap();
-        *timeout_flag_val = false;
-        let ui_account: UiAccount = val.value;
-        let context = val.context;
-        println!("account subscribe context: {:?}", context);
-        match ui_account.data {
-            UiAccountData::Binary(data, encoding) => {
-                println!("Binary data: {:?}, Encoding: {:?}", data, encoding);
-            }
-            UiAccountData::Json(data) => {
-                println!("account subscribe notification: {:?}", data);
-                let record_clone = token_acct_record.clone();
-                let token_acct_clone = record_clone.token_acct.clone();
-                let conn_clone_for_task = Arc::clone(&conn_manager_clone_sub);
-                task::spawn(async move {
-                    let token_acct_update_res = balances::handle_token_acct_change(
-                        conn_clone_for_task,
-                        record_clone,
-                        data,
-                        context,
-                    )
-                    .await;
-                    match token_acct_update_res {
-                        Ok(_) => {
-                            println!("successfully updated token balance: {:?}", token_acct_clone)
+        let mut stale_deadline = Instant::now() + config.subscription_stale_after;
+        let mut failed_checks = 0;
+        loop {
+            let val = tokio::select! {
+                _ = cancel.cancelled() => {
+                    unsubscribe().await;
+                    println!("unsubscribed from token acct: {}", token_acct_pubkey);
+                    return SubscriberExit::Cancelled;
+                }
+                // quiet accounts are normal, so silence alone only triggers a check over http; the
+                // subscription is renewed once it missed a change or can no longer be checked
+                _ = tokio::time::sleep_until(stale_deadline) => {
+                    stale_deadline = Instant::now() + config.subscription_stale_after;
+                    match balance_drifted(&config, pool.clone(), &token_acct_pubkey).await {
+                        Ok(false) => {
+                            failed_checks = 0;
+                            println!(
+                                "no updates for {:?} but balance matches chain, keeping subscription: {}",
+                                config.subscription_stale_after, token_acct_pubkey
+                            );
+                            continue;
+                        }
+                        Ok(true) => eprintln!(
+                            "subscription missed a balance change, resubscribing to token acct: {}",
+                            token_acct_pubkey
+                        ),
+                        Err(e) => {
+                            failed_checks += 1;
+                            eprintln!(
+                                "error checking quiet subscription for token acct {} ({} in a row): {}",
+                                token_acct_pubkey, failed_checks, e
+                            );
+                            if failed_checks < MAX_FAILED_STALENESS_CHECKS {
+                                continue;
+                            }
                         }
-                        Err(e) => println!("error kind: {:?}", e),
                     }
-                });
-            }
-            UiAccountData::LegacyBinary(data) => {
-                println!("Parsed LegacyBinary data: {:?}", data);
+                    unsubscribe().await;
+                    continue 'subscription;
+                }
+                val = subscription.next() => match val {
+                    Some(val) => val,
+                    None => break 'subscription,
+                },
+            };
+            stale_deadline = Instant::now() + config.subscription_stale_after;
+            failed_checks = 0;
+            let ui_account: UiAccount = val.value;
+            let context = val.context;
+            println!("account subscribe context: {:?}", context);
+            match ui_account.data {
+                UiAccountData::Binary(data, encoding) => {
+                    println!("Binary data: {:?}, Encoding: {:?}", data, encoding);
+                }
+                UiAccountData::Json(data) => {
+                    println!("account subscribe notification: {:?}", data);
+                    let record_clone = token_acct_record.clone();
+                    let token_acct_clone = record_clone.token_acct.clone();
+                    let pool_clone_for_task = pool.clone();
+                    let slot = context.slot;
+                    let queued = account_updates
+                        .submit(async move {
+                            let token_acct_update_res = balances::handle_token_acct_change(
+                                pool_clone_for_task,
+                                record_clone,
+                                data,
+                                context,
+                            )
+                            .await;
+                            match token_acct_update_res {
+                                Ok(_) => {
+                                    println!(
+                                        "successfully updated token balance: {:?}",
+                                        token_acct_clone
+                                    )
+                                }
+                                Err(e) => println!("error kind: {:?}", e),
+                            }
+                        })
+                        .await;
+                    if !queued {
+                        eprintln!(
+                            "dropped update for token acct {} at slot {}",
+                            token_acct_pubkey, slot
+                        );
+                    }
+                }
+                UiAccountData::LegacyBinary(data) => {
+                    println!("Parsed LegacyBinary data: {:?}", data);
+                }
             }
         }
     }
-    println!(
-        "end of rpc account subscriber scope: {}",
-        token_acct_pubkey.to_string()
+    println!("end of rpc account subscriber scope: {}", token_acct_pubkey);
+    SubscriberExit::StreamEnded
+}
+
+/// Whether the balance on chain differs from the one we last recorded for the token acct.
+async fn balance_drifted(
+    config: &Config,
+    pool: Pool,
+    token_acct_pubkey: &Pubkey,
+) -> Result<bool, AssetWatcherError> {
+    let rpc_client = Arc::new(
+        solana_client::nonblocking::rpc_client::RpcClient::new_with_commitment(
+            config.rpc_endpoint_http.clone(),
+            config.commitment,
+        ),
     );
+    let token_account = adapters::rpc::get_token_account_by_address(
+        rpc_client,
+        token_acct_pubkey.to_string(),
+        config.commitment,
+    )
+    .await?;
 
-    // enabling this seems questionable now... since when this function fires we will not reset things to
-    // update_token_acct_with_status(
-    //     token_acct_pubkey.to_string(),
-    //     TokenAcctStatus::Enabled,
-    //     conn_manager,
-    // )
-    // .await;
+    let token_acct_str = token_acct_pubkey.to_string();
+    let recorded_amount: BigDecimal = pool
+        .get()
+        .await?
+        .interact(move |db: &mut PgConnection| {
+            token_accts::table
+                .filter(token_accts::token_acct.eq(token_acct_str))
+                .select(token_accts::amount)
+                .first::<BigDecimal>(db)
+        })
+        .await??;
+    Ok(recorded_amount != BigDecimal::from(token_account.amount))
 }
 
 async fn check_and_update_initial_balance(
-    rpc_endpoint: String,
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    config: &Config,
+    pool: Pool,
     token_acct_pubkey: &Pubkey,
-    token_acct_record: &TokenAcct,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), AssetWatcherError> {
+    // read the row again, since the record the subscriber started with is stale after any update
+    let token_acct_str = token_acct_pubkey.to_string();
+    let token_acct_record: TokenAcct = pool
+        .get()
+        .await?
+        .interact(move |db: &mut PgConnection| {
+            token_accts::table
+                .filter(token_accts::token_acct.eq(token_acct_str))
+                .first::<TokenAcct>(db)
+                .optional()
+        })
+        .await??
+        .ok_or_else(|| AssetWatcherError::MissingTokenRecord(token_acct_pubkey.to_string()))?;
+
+    let rpc_client = Arc::new(
+        solana_client::nonblocking::rpc_client::RpcClient::new_with_commitment(
+            config.rpc_endpoint_http.clone(),
+            config.commitment,
+        ),
+    );
+
+    // replay anything missed since the last recorded balance, e.g. while we were down
+    let backfilled = balance_history::backfill_token_acct_gap(
+        pool.clone(),
+        &rpc_client,
+        &token_acct_record,
+        config.gap_backfill_max_signatures,
+    )
+    .await?;
+    if backfilled > 0 {
+        println!(
+            "backfilled {} missed balance changes for token acct: {}",
+            backfilled, token_acct_pubkey
+        );
+    }
 
-    let rpc_client = Arc::new(solana_client::nonblocking::rpc_client::RpcClient::new(
-        rpc_endpoint,
-    ));
     let token_account = adapters::rpc::get_token_account_by_address(
         Arc::clone(&rpc_client),
         token_acct_pubkey.to_string(),
+        config.commitment,
     )
     .await?;
     let balance = BigDecimal::from(token_account.amount);
@@ -152,7 +271,9 @@ async fn check_and_update_initial_balance(
         if let Some(latest_tx_info) = latest_tx.first() {
             let transaction_sig = latest_tx_info.signature.clone();
             let transaction_sig_2 = latest_tx_info.signature.clone();
-            let transaction_exists: Option<Transaction> = conn_manager
+            let transaction_exists: Option<Transaction> = pool
+                .get()
+                .await?
                 .interact(move |db: &mut PgConnection| {
                     transactions::table
                         .filter(tx_sig.eq(transaction_sig.clone()))
@@ -172,7 +293,7 @@ async fn check_and_update_initial_balance(
             };
 
             handle_token_acct_balance_tx(
-                conn_manager,
+                pool,
                 token_acct_pubkey.to_string(),
                 balance,
                 transaction_sig_option,
@@ -183,45 +304,6 @@ async fn check_and_update_initial_balance(
             .await?;
         }
     }
-    
 
     Ok(())
 }
-
-// TODO this should return a result and be handled appropriately..
-async fn update_token_acct_with_status(
-    token_acct: String,
-    status: TokenAcctStatus,
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
-) {
-    let token_acct_query = token_acct.clone();
-    let status_for_set = status.clone();
-    let res = conn_manager
-        .interact(move |db| {
-            update(
-                token_accts::table.filter(token_accts::token_acct.eq(token_acct_query.to_string())),
-            )
-            .set((
-                token_accts::dsl::status.eq(status_for_set),
-                token_accts::dsl::updated_at.eq(Utc::now()),
-            ))
-            .get_result::<TokenAcct>(db)
-        })
-        .await;
-
-    match res {
-        Ok(Ok(_)) => println!(
-            "updated token acct to {:?} status: {}",
-            status,
-            token_acct.to_string()
-        ),
-        Err(e) => eprintln!(
-            "error updating token acct [{}] to {:?} status: {}",
-            token_acct, status, e
-        ),
-        Ok(Err(e)) => eprintln!(
-            "error updating token acct [{}] to {:?} status: {}",
-            token_acct, status, e
-        ),
-    }
-}
@@ -1,111 +1,329 @@
+use crate::config::Config;
+use crate::entities::indexed_transactions::indexed_transactions;
 use crate::entities::token_accts::{token_accts, TokenAcct, TokenAcctStatus};
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
+use crate::entities::transactions::{transactions, Transaction};
+use crate::entities::watch_targets::{watch_targets, WatchTarget};
+use crate::entrypoints::events::backoff::with_backoff;
+use crate::entrypoints::events::notification_handler::NotificationRegistry;
+use crate::entrypoints::events::subscriptions::SubscriptionManager;
+use crate::entrypoints::events::token_accts_insert::TokenAcctsInsertHandler;
+use crate::entrypoints::events::token_accts_status_update::TokenAcctsStatusUpdateHandler;
+use crate::entrypoints::events::transactions_insert::TransactionsInsertHandler;
+use crate::entrypoints::events::worker_pool::{EventWorkers, WorkerPool};
+use crate::errors::AssetWatcherError;
+use crate::shutdown::Shutdown;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Duration, Utc};
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
-use diesel::PgConnection;
-use futures::{stream, FutureExt, StreamExt, TryStreamExt};
+use futures::{stream, FutureExt, StreamExt};
+use futures_channel::mpsc::UnboundedReceiver;
 use postgres::NoTls;
-use solana_client::nonblocking::pubsub_client::PubsubClient;
-use solana_sdk::pubkey::Pubkey;
-use std::str::FromStr;
 use std::sync::Arc;
-use tokio::task;
+use tokio::sync::Notify;
 use tokio_postgres::{connect, AsyncMessage};
 
+// transactions can land in the table a while after their block time
+const CATCH_UP_MARGIN: Duration = Duration::minutes(10);
+const CATCH_UP_PAGE_SIZE: i64 = 500;
+
 // TODO this should return a result
 pub async fn setup_event_listeners(
-    db_url: &str,
-    managed_connection: Arc<Object<Manager<PgConnection>>>,
-    pub_sub_client: Arc<PubsubClient>,
+    config: Arc<Config>,
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+    workers: EventWorkers,
+    shutdown: Shutdown,
 ) {
     // account subscribe for token_accts already in Watching status
-    let token_accts_res = managed_connection
-        .clone()
-        .interact(|conn| {
-            return token_accts::table
-                .filter(token_accts::status.eq(TokenAcctStatus::Watching))
-                .load::<TokenAcct>(conn)
-                .expect("Error loading token_accts");
-        })
-        .await;
-
-    match token_accts_res {
+    match load_watching_token_accts(&pool).await {
         Ok(token_accts_vec) => {
             for record in token_accts_vec {
-                match Pubkey::from_str(&record.token_acct) {
-                    Ok(token_acct_pubkey) => {
-                        let conn_manager_arg_clone = Arc::clone(&managed_connection);
-                        let pub_sub_client_clone = Arc::clone(&pub_sub_client);
-                        println!(
-                            "spawning task for token acct subscription: {}",
-                            token_acct_pubkey.to_string()
-                        );
-                        task::spawn(async move {
-                            println!(
-                                "task running for token acct subscription: {}",
-                                token_acct_pubkey.to_string()
-                            );
-                            super::rpc_token_acct_updates::new_handler(
-                                pub_sub_client_clone,
-                                conn_manager_arg_clone,
-                                token_acct_pubkey,
-                                record,
-                            )
-                            .await
-                        });
-                    }
-                    Err(e) => eprintln!("Error with token acct pubkey parsing: {}", e),
+                if let Err(e) = subscriptions.subscribe(record) {
+                    eprintln!("Error with token acct subscription: {}", e);
                 }
             }
         }
         Err(e) => eprintln!("Error with subscribing to token accts: {}", e),
     }
+    // program subscribe for owner wallets and mints already in Watching status
+    match load_watching_targets(&pool).await {
+        Ok(targets) => {
+            for target in targets {
+                if let Err(e) = subscriptions.watch_target(&target) {
+                    eprintln!("Error with watch target subscription: {}", e);
+                }
+            }
+        }
+        Err(e) => eprintln!("Error with subscribing to watch targets: {}", e),
+    }
 
-    // listen to postgres notifications
-    let (client, mut connection) = connect(db_url, NoTls).await.unwrap();
-    // Make transmitter and receiver.
-    let (tx, mut rx) = futures_channel::mpsc::unbounded();
-    let stream =
-        stream::poll_fn(move |cx| connection.poll_message(cx)).map_err(|e| panic!("{}", e));
-    let connection = stream.forward(tx).map(|r| r.unwrap());
-    tokio::spawn(connection);
-
-    client
-        .batch_execute(
-            "
-            LISTEN token_accts_insert_channel;
-            LISTEN token_accts_status_update_channel;
-            LISTEN transactions_insert_channel;
-            ",
+    // rebuild the websocket client and resubscribe if the connection drops
+    shutdown.spawn(super::pubsub_supervisor::run(
+        Arc::clone(&config),
+        pool.clone(),
+        subscriptions.clone(),
+        shutdown.clone(),
+    ));
+
+    let registry = NotificationRegistry::new()
+        .register(
+            TokenAcctsInsertHandler {
+                pool: pool.clone(),
+                subscriptions: subscriptions.clone(),
+            },
+            workers.token_accts.clone(),
+        )
+        .register(
+            TokenAcctsStatusUpdateHandler {
+                pool: pool.clone(),
+                subscriptions: subscriptions.clone(),
+            },
+            workers.token_accts.clone(),
         )
-        .await
-        .unwrap();
-
-    while let Some(m) = rx.next().await {
-        let connect_clone = Arc::clone(&managed_connection);
-        match m {
-            AsyncMessage::Notification(n) => match n.channel() {
-                "token_accts_insert_channel" => {
-                    task::spawn(super::token_accts_insert::new_handler(
-                        n,
-                        connect_clone,
-                        Arc::clone(&pub_sub_client),
-                    ));
+        .register(
+            TransactionsInsertHandler { pool: pool.clone() },
+            workers.transactions.clone(),
+        );
+
+    // consume events written to the outbox, including any raised while we were down
+    let outbox_wake = Arc::new(Notify::new());
+    shutdown.spawn(super::outbox::run_consumer(
+        Arc::clone(&config),
+        pool.clone(),
+        registry.clone(),
+        Arc::clone(&outbox_wake),
+        shutdown.clone(),
+    ));
+
+    // nothing was listening before startup either, so the first connection catches up from the
+    // block time of the last indexed tx the same way a reconnect catches up from the disconnect
+    let mut disconnected_at: Option<DateTime<Utc>> = match load_last_indexed_block_time(&pool).await
+    {
+        Ok(last_indexed_block_time) => last_indexed_block_time,
+        Err(e) => {
+            eprintln!("Error loading last indexed tx for catch up: {}", e);
+            None
+        }
+    };
+
+    // listen to postgres notifications, reconnecting whenever the connection drops
+    loop {
+        let connected = with_backoff(&config, &shutdown, "postgres listen connection", || {
+            connect_and_listen(&config.database_url, &registry)
+        })
+        .await;
+        let Some((client, mut rx)) = connected else {
+            return;
+        };
+        println!("listening for postgres notifications");
+
+        if let Some(since) = disconnected_at.take() {
+            shutdown.spawn(catch_up(
+                pool.clone(),
+                subscriptions.clone(),
+                workers.transactions.clone(),
+                since,
+            ));
+        }
+        outbox_wake.notify_one();
+
+        loop {
+            let m = tokio::select! {
+                _ = shutdown.cancelled() => {
+                    println!("shutdown requested, no longer listening for postgres notifications");
+                    return;
                 }
-                "transactions_insert_channel" => {
-                    task::spawn(super::transactions_insert::new_handler(n, connect_clone));
+                m = rx.next() => match m {
+                    Some(Ok(m)) => m,
+                    Some(Err(e)) => {
+                        eprintln!("postgres notification connection error: {}", e);
+                        break;
+                    }
+                    None => break,
+                },
+            };
+            match m {
+                // events themselves are read from the outbox, a notification only means there is work
+                AsyncMessage::Notification(n) => {
+                    if registry.handles(n.channel()) {
+                        outbox_wake.notify_one();
+                    }
                 }
-                "token_accts_status_update_channel" => {
-                    task::spawn(super::token_accts_status_update::new_handler(
-                        n,
-                        connect_clone,
-                        Arc::clone(&pub_sub_client),
-                    ));
+                AsyncMessage::Notice(notice) => println!("async message error: {:?}", notice),
+                _ => println!("fallthrough handler of async message from postgres listener"),
+            }
+        }
+        drop(client);
+        disconnected_at = Some(Utc::now());
+        eprintln!("lost postgres notification connection, reconnecting");
+    }
+}
+
+type NotificationReceiver = UnboundedReceiver<Result<AsyncMessage, tokio_postgres::Error>>;
+
+/// Opens a dedicated connection and issues LISTEN for every registered channel.
+async fn connect_and_listen(
+    database_url: &str,
+    registry: &NotificationRegistry,
+) -> Result<(tokio_postgres::Client, NotificationReceiver), AssetWatcherError> {
+    let (client, mut connection) = connect(database_url, NoTls).await?;
+    // Make transmitter and receiver.
+    let (tx, rx) = futures_channel::mpsc::unbounded();
+    let stream = stream::poll_fn(move |cx| connection.poll_message(cx)).map(Ok);
+    // ends, dropping tx, once the connection closes, which ends rx for the listen loop
+    tokio::spawn(stream.forward(tx).map(|_| ()));
+
+    let listen_statements: String = registry
+        .channels()
+        .iter()
+        .map(|channel| format!("LISTEN {};", channel))
+        .collect();
+    client.batch_execute(&listen_statements).await?;
+    Ok((client, rx))
+}
+
+/**
+ * Picks up work that may have been missed while the listen connection was down: accounts that
+ * were switched to Watching, and transactions inserted since the disconnect. Transactions are
+ * matched on block_time, so the window reaches back a bit to cover rows inserted with lag.
+ * They are loaded a page at a time in (slot, tx_sig) order and queued on the transactions worker
+ * pool like their notifications would be, so waiting for room also paces the loading.
+ */
+async fn catch_up(
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+    transaction_workers: WorkerPool,
+    since: DateTime<Utc>,
+) {
+    match load_watching_token_accts(&pool).await {
+        Ok(token_accts_vec) => {
+            for record in token_accts_vec {
+                if let Err(e) = subscriptions.subscribe(record) {
+                    eprintln!("Error with token acct subscription: {}", e);
                 }
-                _ => (),
-            },
-            AsyncMessage::Notice(notice) => println!("async message error: {:?}", notice),
-            _ => println!("fallthrough handler of async message from postgres listener"),
+            }
         }
+        Err(e) => eprintln!("Error loading watching token accts for catch up: {}", e),
     }
+
+    let window_start = since - CATCH_UP_MARGIN;
+    println!("catching up on transactions since {}", window_start);
+    let mut cursor: Option<(BigDecimal, String)> = None;
+    let mut queued = 0;
+    loop {
+        let page = match load_transactions_page(&pool, window_start, cursor.clone()).await {
+            Ok(page) => page,
+            Err(e) => {
+                eprintln!("Error loading transactions for catch up: {}", e);
+                return;
+            }
+        };
+        let Some(last) = page.last() else {
+            break;
+        };
+        cursor = Some((last.slot.clone(), last.tx_sig.clone()));
+
+        for transaction in page {
+            let pool = pool.clone();
+            transaction_workers
+                .submit(async move {
+                    if let Err(e) =
+                        super::transactions_insert::index_tx_record(transaction, pool, false).await
+                    {
+                        eprintln!("Error indexing transaction during catch up: {}", e);
+                    }
+                })
+                .await;
+            queued += 1;
+        }
+    }
+    println!(
+        "queued {} transactions to catch up on since {}",
+        queued, window_start
+    );
+}
+
+/// Block time of the tx most recently run through the indexers, if any ever was.
+async fn load_last_indexed_block_time(
+    pool: &Pool,
+) -> Result<Option<DateTime<Utc>>, AssetWatcherError> {
+    let last_indexed_block_time = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            let last_indexed_sig: Option<String> = indexed_transactions::table
+                .select(indexed_transactions::tx_sig)
+                .order(indexed_transactions::indexed_at.desc())
+                .first(conn)
+                .optional()?;
+            let Some(last_indexed_sig) = last_indexed_sig else {
+                return Ok(None);
+            };
+            transactions::table
+                .filter(transactions::tx_sig.eq(last_indexed_sig))
+                .select(transactions::block_time)
+                .first::<DateTime<Utc>>(conn)
+                .optional()
+        })
+        .await??;
+    Ok(last_indexed_block_time)
+}
+
+/// The next page of transactions since `window_start` after the (slot, tx_sig) cursor.
+async fn load_transactions_page(
+    pool: &Pool,
+    window_start: DateTime<Utc>,
+    cursor: Option<(BigDecimal, String)>,
+) -> Result<Vec<Transaction>, AssetWatcherError> {
+    let page = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            let mut query = transactions::table
+                .filter(transactions::main_ix_type.is_not_null())
+                .filter(transactions::block_time.ge(window_start))
+                .into_boxed();
+            if let Some((last_slot, last_tx_sig)) = cursor {
+                query = query.filter(
+                    transactions::slot
+                        .gt(last_slot.clone())
+                        .or(transactions::slot
+                            .eq(last_slot)
+                            .and(transactions::tx_sig.gt(last_tx_sig))),
+                );
+            }
+            query
+                .order((transactions::slot.asc(), transactions::tx_sig.asc()))
+                .limit(CATCH_UP_PAGE_SIZE)
+                .load::<Transaction>(conn)
+        })
+        .await??;
+    Ok(page)
+}
+
+pub async fn load_watching_token_accts(pool: &Pool) -> Result<Vec<TokenAcct>, AssetWatcherError> {
+    let token_accts_vec = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            token_accts::table
+                .filter(token_accts::status.eq(TokenAcctStatus::Watching))
+                .load::<TokenAcct>(conn)
+        })
+        .await??;
+    Ok(token_accts_vec)
+}
+
+pub async fn load_watching_targets(pool: &Pool) -> Result<Vec<WatchTarget>, AssetWatcherError> {
+    let targets = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            watch_targets::table
+                .filter(watch_targets::status.eq(TokenAcctStatus::Watching))
+                .load::<WatchTarget>(conn)
+        })
+        .await??;
+    Ok(targets)
 }
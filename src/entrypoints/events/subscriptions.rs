@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Utc};
+use deadpool_diesel::postgres::Pool;
+use serde::Serialize;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Config;
+use crate::entities::token_accts::TokenAcct;
+use crate::entities::watch_targets::{WatchTarget, WatchTargetType};
+use crate::entrypoints::events::worker_pool::WorkerPool;
+use crate::entrypoints::events::{rpc_program_updates, rpc_token_acct_updates};
+use crate::errors::AssetWatcherError;
+use crate::shutdown::Shutdown;
+
+/**
+ * Owns every live subscription, keyed by token acct pubkey or by watch target.
+ * Subscribing an account that already has a live subscription is a no-op, and unsubscribing
+ * cancels the subscriber task so the websocket stream is actually closed.
+ * Entries remove themselves when their subscriber task ends for any reason.
+ *
+ * The pubsub client can be swapped out after a websocket drop. Each client gets a generation
+ * number so a subscriber that ends on an old client does not trigger a second reconnect.
+ */
+#[derive(Clone)]
+pub struct SubscriptionManager {
+    config: Arc<Config>,
+    pool: Pool,
+    client: Arc<RwLock<ClientSlot>>,
+    account_updates: WorkerPool,
+    shutdown: Shutdown,
+    subscriptions: Arc<Mutex<HashMap<SubscriptionKey, SubscriptionHandle>>>,
+    next_id: Arc<AtomicU64>,
+    disconnected_generation: Arc<AtomicU64>,
+    disconnected: Arc<Notify>,
+}
+
+struct ClientSlot {
+    generation: u64,
+    pub_sub_client: Arc<PubsubClient>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SubscriptionKey {
+    TokenAcct(Pubkey),
+    Target(WatchTargetType, Pubkey),
+}
+
+enum Subscriber {
+    TokenAcct(Pubkey, TokenAcct),
+    Target(WatchTargetType, Pubkey),
+}
+
+impl Subscriber {
+    fn key(&self) -> SubscriptionKey {
+        match self {
+            Subscriber::TokenAcct(token_acct_pubkey, _) => {
+                SubscriptionKey::TokenAcct(*token_acct_pubkey)
+            }
+            Subscriber::Target(target_type, target_pubkey) => {
+                SubscriptionKey::Target(*target_type, *target_pubkey)
+            }
+        }
+    }
+}
+
+/// Why a subscriber task returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriberExit {
+    Cancelled,
+    /// the notification stream closed, which only happens when the websocket is gone
+    StreamEnded,
+}
+
+struct SubscriptionHandle {
+    // distinguishes a replaced entry from the one a finishing task should remove
+    id: u64,
+    cancel: CancellationToken,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionInfo {
+    pub kind: String,
+    pub account: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+impl SubscriptionManager {
+    pub fn new(
+        config: Arc<Config>,
+        pool: Pool,
+        pub_sub_client: Arc<PubsubClient>,
+        account_updates: WorkerPool,
+        shutdown: Shutdown,
+    ) -> Self {
+        SubscriptionManager {
+            config,
+            pool,
+            client: Arc::new(RwLock::new(ClientSlot {
+                generation: 1,
+                pub_sub_client,
+            })),
+            account_updates,
+            shutdown,
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+            disconnected_generation: Arc::new(AtomicU64::new(0)),
+            disconnected: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Starts an account subscription for the record. Returns false if one was already live.
+    pub fn subscribe(&self, token_acct_record: TokenAcct) -> Result<bool, AssetWatcherError> {
+        let token_acct_pubkey = Pubkey::from_str(&token_acct_record.token_acct)?;
+        if self.shutdown.is_triggered() {
+            return Ok(false);
+        }
+
+        Ok(self.subscribe_once(Subscriber::TokenAcct(token_acct_pubkey, token_acct_record)))
+    }
+
+    /// Starts a program subscription for the watch target. Returns false if one was already live.
+    pub fn watch_target(&self, target: &WatchTarget) -> Result<bool, AssetWatcherError> {
+        let target_pubkey = Pubkey::from_str(&target.target_acct)?;
+        if self.shutdown.is_triggered() {
+            return Ok(false);
+        }
+        Ok(self.subscribe_once(Subscriber::Target(target.target_type, target_pubkey)))
+    }
+
+    fn subscribe_once(&self, subscriber: Subscriber) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscriptions.contains_key(&subscriber.key()) {
+            println!("already subscribed, ignoring: {:?}", subscriber.key());
+            return false;
+        }
+        self.spawn_subscriber(&mut subscriptions, subscriber);
+        true
+    }
+
+    /// Cancels the account subscription if there is one. Returns false if nothing was subscribed.
+    pub fn unsubscribe(&self, token_acct: &str) -> Result<bool, AssetWatcherError> {
+        let token_acct_pubkey = Pubkey::from_str(token_acct)?;
+        Ok(self.cancel(SubscriptionKey::TokenAcct(token_acct_pubkey)))
+    }
+
+    /// Cancels the watch target's program subscription. Returns false if nothing was subscribed.
+    pub fn unwatch_target(
+        &self,
+        target_type: WatchTargetType,
+        target_acct: &str,
+    ) -> Result<bool, AssetWatcherError> {
+        let target_pubkey = Pubkey::from_str(target_acct)?;
+        Ok(self.cancel(SubscriptionKey::Target(target_type, target_pubkey)))
+    }
+
+    fn cancel(&self, key: SubscriptionKey) -> bool {
+        match self.subscriptions.lock().unwrap().remove(&key) {
+            Some(handle) => {
+                handle.cancel.cancel();
+                println!("unsubscribing: {:?}", key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Swaps in a freshly connected client and replaces every subscription with one on it.
+     * Subscribers still attached to the old client are cancelled first.
+     */
+    pub fn replace_client(
+        &self,
+        pub_sub_client: Arc<PubsubClient>,
+        records: Vec<TokenAcct>,
+        targets: Vec<WatchTarget>,
+    ) {
+        {
+            let mut slot = self.client.write().unwrap();
+            slot.generation += 1;
+            slot.pub_sub_client = pub_sub_client;
+        }
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        for (_, handle) in subscriptions.drain() {
+            handle.cancel.cancel();
+        }
+        if self.shutdown.is_triggered() {
+            return;
+        }
+        for record in records {
+            match Pubkey::from_str(&record.token_acct) {
+                Ok(token_acct_pubkey) => self.spawn_subscriber(
+                    &mut subscriptions,
+                    Subscriber::TokenAcct(token_acct_pubkey, record),
+                ),
+                Err(e) => eprintln!("Error with token acct pubkey parsing: {}", e),
+            }
+        }
+        for target in targets {
+            match Pubkey::from_str(&target.target_acct) {
+                Ok(target_pubkey) => self.spawn_subscriber(
+                    &mut subscriptions,
+                    Subscriber::Target(target.target_type, target_pubkey),
+                ),
+                Err(e) => eprintln!("Error with watch target pubkey parsing: {}", e),
+            }
+        }
+    }
+
+    /// Resolves once a subscription stream on the current client has ended without being cancelled.
+    pub async fn wait_for_disconnect(&self) {
+        loop {
+            self.disconnected.notified().await;
+            let current_generation = self.client.read().unwrap().generation;
+            if self.disconnected_generation.load(Ordering::SeqCst) == current_generation {
+                return;
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<SubscriptionInfo> {
+        let mut subscriptions: Vec<SubscriptionInfo> = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, handle)| {
+                let (kind, account) = match key {
+                    SubscriptionKey::TokenAcct(token_acct_pubkey) => {
+                        ("token_acct".to_string(), token_acct_pubkey.to_string())
+                    }
+                    SubscriptionKey::Target(target_type, target_pubkey) => {
+                        (target_type.to_string(), target_pubkey.to_string())
+                    }
+                };
+                SubscriptionInfo {
+                    kind,
+                    account,
+                    subscribed_at: handle.subscribed_at,
+                }
+            })
+            .collect();
+        subscriptions.sort_by(|a, b| (&a.kind, &a.account).cmp(&(&b.kind, &b.account)));
+        subscriptions
+    }
+
+    fn spawn_subscriber(
+        &self,
+        subscriptions: &mut HashMap<SubscriptionKey, SubscriptionHandle>,
+        subscriber: Subscriber,
+    ) {
+        let key = subscriber.key();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancel = self.shutdown.child_token();
+        subscriptions.insert(
+            key,
+            SubscriptionHandle {
+                id,
+                cancel: cancel.clone(),
+                subscribed_at: Utc::now(),
+            },
+        );
+
+        println!("spawning task for subscription: {:?}", key);
+        let (generation, pub_sub_client) = {
+            let slot = self.client.read().unwrap();
+            (slot.generation, Arc::clone(&slot.pub_sub_client))
+        };
+        let config = Arc::clone(&self.config);
+        let pool = self.pool.clone();
+        let account_updates = self.account_updates.clone();
+        let manager = self.clone();
+        self.shutdown.spawn(async move {
+            let exit = match subscriber {
+                Subscriber::TokenAcct(token_acct_pubkey, token_acct_record) => {
+                    rpc_token_acct_updates::new_handler(
+                        config,
+                        pub_sub_client,
+                        pool,
+                        token_acct_pubkey,
+                        token_acct_record,
+                        account_updates,
+                        cancel.clone(),
+                    )
+                    .await
+                }
+                Subscriber::Target(target_type, target_pubkey) => {
+                    rpc_program_updates::new_handler(
+                        config,
+                        pub_sub_client,
+                        pool,
+                        target_type,
+                        target_pubkey,
+                        account_updates,
+                        cancel.clone(),
+                    )
+                    .await
+                }
+            };
+            {
+                let mut subscriptions = manager.subscriptions.lock().unwrap();
+                if subscriptions
+                    .get(&key)
+                    .is_some_and(|handle| handle.id == id)
+                {
+                    subscriptions.remove(&key);
+                }
+            }
+            if exit == SubscriberExit::StreamEnded && !cancel.is_cancelled() {
+                manager
+                    .disconnected_generation
+                    .fetch_max(generation, Ordering::SeqCst);
+                manager.disconnected.notify_one();
+            }
+        });
+    }
+}
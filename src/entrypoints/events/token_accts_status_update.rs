@@ -2,74 +2,65 @@ use crate::entities::token_accts::token_accts;
 use crate::entities::token_accts::TokenAcct;
 use crate::entities::token_accts::TokenAcctStatus;
 use crate::entities::token_accts::TokenAcctsStatusUpdateChannelPayload;
-use crate::entrypoints::events::rpc_token_acct_updates;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
+use crate::entrypoints::events::notification_handler::NotificationHandler;
+use crate::entrypoints::events::subscriptions::SubscriptionManager;
+use async_trait::async_trait;
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
-use diesel::PgConnection;
-use postgres::Notification;
-use solana_client::nonblocking::pubsub_client::PubsubClient;
-use solana_sdk::pubkey::Pubkey;
-use std::str::FromStr;
-use std::sync::Arc;
 
 use crate::entities::token_accts::token_accts::dsl::*;
+use crate::errors::AssetWatcherError;
 
-pub async fn new_handler(
-    notification: Notification,
-    pool_connection: Arc<Object<Manager<PgConnection>>>,
-    pub_sub_rpc_client: Arc<PubsubClient>,
-) {
-    println!(
-        "new token_accts_status_update payload: {:?}",
-        notification.payload()
-    );
-    match handle_update_token_acct_status_notification(
-        pool_connection,
-        notification,
-        Arc::clone(&pub_sub_rpc_client),
-    )
-    .await
-    {
-        Ok(()) => println!("successfully handled token_acct status update notification"),
-        Err(e) => eprintln!("error token_acct status update notification: {:?}", e),
-    };
+pub struct TokenAcctsStatusUpdateHandler {
+    pub pool: Pool,
+    pub subscriptions: SubscriptionManager,
+}
+
+#[async_trait]
+impl NotificationHandler for TokenAcctsStatusUpdateHandler {
+    type Payload = TokenAcctsStatusUpdateChannelPayload;
+
+    fn channel(&self) -> &'static str {
+        "token_accts_status_update_channel"
+    }
+
+    async fn handle(
+        &self,
+        token_acct_payload: TokenAcctsStatusUpdateChannelPayload,
+    ) -> Result<(), AssetWatcherError> {
+        handle_update_token_acct_status_notification(
+            self.pool.clone(),
+            token_acct_payload,
+            self.subscriptions.clone(),
+        )
+        .await
+    }
 }
 
 async fn handle_update_token_acct_status_notification(
-    pool_connection: Arc<Object<Manager<PgConnection>>>,
-    notification: Notification,
-    pub_sub_rpc_client: Arc<PubsubClient>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let cloned_connection = Arc::clone(&pool_connection);
-    let token_acct_payload =
-        TokenAcctsStatusUpdateChannelPayload::parse_payload(notification.payload())?;
+    pool: Pool,
+    token_acct_payload: TokenAcctsStatusUpdateChannelPayload,
+    subscriptions: SubscriptionManager,
+) -> Result<(), AssetWatcherError> {
     if token_acct_payload.status != TokenAcctStatus::Watching {
+        // Enabled and Disabled accounts should not hold a websocket subscription
+        subscriptions.unsubscribe(&token_acct_payload.token_acct)?;
         return Ok(());
     }
     let token_acct_string = token_acct_payload.token_acct;
     let token_acct_clone = token_acct_string.clone();
-    let token_acct_record: TokenAcct = cloned_connection
-        .clone()
+    let token_acct_record: TokenAcct = pool
+        .get()
+        .await?
         .interact(move |conn| {
-            return token_accts
+            token_accts
                 .filter(token_accts::dsl::token_acct.eq(&token_acct_clone))
                 .first(conn)
-                .expect("could not find token record");
+                .optional()
         })
-        .await?;
-    let token_acct_pubkey = Pubkey::from_str(&token_acct_string)?;
-    let pub_sub_client_clone = Arc::clone(&pub_sub_rpc_client);
-
-    tokio::spawn(async move {
-        rpc_token_acct_updates::new_handler(
-            pub_sub_client_clone,
-            Arc::clone(&cloned_connection),
-            token_acct_pubkey,
-            token_acct_record.clone(),
-        )
-        .await
-    });
+        .await??
+        .ok_or_else(|| AssetWatcherError::MissingTokenRecord(token_acct_string.clone()))?;
+    subscriptions.subscribe(token_acct_record)?;
 
     Ok(())
 }
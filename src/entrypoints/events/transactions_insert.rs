@@ -1,200 +1,384 @@
+use crate::entities::indexed_transactions::{
+    indexed_transactions, IndexOutcome, IndexedTransaction,
+};
 use crate::entities::transactions::{InstructionType, Payload, TransactionsInsertChannelPayload};
+use crate::entrypoints::events::notification_handler::NotificationHandler;
 use crate::services;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
+use async_trait::async_trait;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
-use diesel::{ExpressionMethods, PgConnection};
-use futures::executor::block_on;
-use postgres::Notification;
-use std::sync::Arc;
+use diesel::ExpressionMethods;
 
 use crate::entities::transactions::{transactions::dsl::*, Transaction};
+use crate::errors::AssetWatcherError;
 
-pub async fn new_handler(
-    notification: Notification,
-    pool_connection: Arc<Object<Manager<PgConnection>>>,
-) {
-    println!(
-        "new transactions table payload: {:?}",
-        notification.payload()
-    );
-    match TransactionsInsertChannelPayload::parse_payload(notification.payload()) {
-        Ok(tx_payload) => match handle_new_transaction(tx_payload.tx_sig, pool_connection).await {
-            Ok(()) => println!("successfully handled new transaction notification"),
-            Err(e) => eprintln!("error handling new transaction notification: {:?}", e),
-        },
-        Err(e) => eprintln!("error parsing new transaction notification: {:?}", e),
-    };
+pub struct TransactionsInsertHandler {
+    pub pool: Pool,
+}
+
+#[async_trait]
+impl NotificationHandler for TransactionsInsertHandler {
+    type Payload = TransactionsInsertChannelPayload;
+
+    fn channel(&self) -> &'static str {
+        "transactions_insert_channel"
+    }
+
+    async fn handle(
+        &self,
+        tx_payload: TransactionsInsertChannelPayload,
+    ) -> Result<(), AssetWatcherError> {
+        handle_new_transaction(tx_payload.tx_sig, self.pool.clone()).await
+    }
 }
 
 async fn handle_new_transaction(
     transaction_signature: String,
-    connection: Arc<Object<Manager<PgConnection>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let txn_result = connection
-        .clone()
+    pool: Pool,
+) -> Result<(), AssetWatcherError> {
+    let transaction_signature_query = transaction_signature.clone();
+    let txn_result = pool
+        .get()
+        .await?
         .interact(|conn| {
-            return transactions
-                .filter(tx_sig.eq(transaction_signature))
+            transactions
+                .filter(tx_sig.eq(transaction_signature_query))
                 .limit(1)
                 .select(Transaction::as_select())
-                .load(conn);
+                .load(conn)
         })
         .await?;
 
     let txn_vec: Vec<Transaction> = txn_result?;
-    let txn: &Transaction = &txn_vec[0];
+    // the row may be gone by the time a replayed event is handled
+    let txn = txn_vec
+        .into_iter()
+        .next()
+        .ok_or(AssetWatcherError::MissingTransaction(transaction_signature))?;
 
-    index_tx_record(txn.clone(), connection).await?;
+    index_tx_record(txn, pool, false).await?;
 
     Ok(())
 }
 
-pub async fn index_tx_record(     
+/// What each sub-indexer made of a transaction, one entry per instruction handler that ran.
+#[derive(Debug)]
+pub struct IndexingReport {
+    pub tx_sig: String,
+    pub main_ix_type: Option<InstructionType>,
+    pub results: Vec<(&'static str, Result<(), AssetWatcherError>)>,
+    /// the ledger already had it at the current indexer version, so nothing ran
+    pub skipped: bool,
+}
+
+impl IndexingReport {
+    fn empty(transaction_sig: String, ix_type: Option<InstructionType>, skipped: bool) -> Self {
+        IndexingReport {
+            tx_sig: transaction_sig,
+            main_ix_type: ix_type,
+            results: vec![],
+            skipped,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.results.iter().all(|(_, res)| res.is_ok())
+    }
+
+    fn outcome(&self) -> IndexOutcome {
+        if self.results.is_empty() {
+            IndexOutcome::Unhandled
+        } else if self.succeeded() {
+            IndexOutcome::Indexed
+        } else {
+            IndexOutcome::Failed
+        }
+    }
+
+    fn failures(&self) -> impl Iterator<Item = (&'static str, &AssetWatcherError)> {
+        self.results
+            .iter()
+            .filter_map(|(label, res)| res.as_ref().err().map(|e| (*label, e)))
+    }
+
+    fn error_summary(&self) -> Option<String> {
+        let errors: Vec<String> = self
+            .failures()
+            .map(|(label, e)| format!("{}: {}", label, e))
+            .collect();
+        (!errors.is_empty()).then(|| errors.join("; "))
+    }
+
+    fn log(&self, payload_parsed: &Payload) {
+        for (label, res) in &self.results {
+            log_index_result(label, res, payload_parsed);
+        }
+        let failures = self.results.iter().filter(|(_, res)| res.is_err()).count();
+        println!(
+            "indexed tx {} ({:?}): {} handled, {} failed",
+            self.tx_sig,
+            self.main_ix_type,
+            self.results.len() - failures,
+            failures
+        );
+    }
+}
+
+/**
+ * Runs the indexers for the transaction's main instruction type and reports each one's result.
+ * Only an unparseable payload or a failed ledger lookup is an error here; failures of individual
+ * indexers are in the report. The outcome is recorded in indexed_transactions, and a tx that
+ * already has a non-failed entry at the current indexer version is skipped unless `force` is set.
+ * A tx with failed indexers is dead lettered for a later retry, and taken out once it succeeds.
+ */
+pub async fn index_tx_record(
     tx: Transaction,
-    connection: Arc<Object<Manager<PgConnection>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    pool: Pool,
+    force: bool,
+) -> Result<IndexingReport, AssetWatcherError> {
+    let transaction_sig = tx.tx_sig;
+    let Some(ix_type) = tx.main_ix_type else {
+        println!("tx has no ix type we care about");
+        return Ok(IndexingReport::empty(transaction_sig, None, false));
+    };
+    let indexer_version = ix_type.indexer_version();
+    if !force && already_indexed(pool.clone(), &transaction_sig, indexer_version).await? {
+        println!(
+            "tx {} already indexed at version {}, skipping",
+            transaction_sig, indexer_version
+        );
+        return Ok(IndexingReport::empty(transaction_sig, Some(ix_type), true));
+    }
     let payload_parsed = Payload::parse_payload(&tx.payload)?;
+    let ledger_pool = pool.clone();
+    let dead_letter_pool = pool.clone();
 
-    match tx.main_ix_type {
-        Some(ix_type) => match ix_type {
-            InstructionType::VaultMintAndAmmSwap => {
-                index_mint_ix(Arc::clone(&connection), &payload_parsed, tx.tx_sig.clone()).await;
-                index_swap_ix(connection, &payload_parsed, tx.tx_sig).await;
-            }
-            InstructionType::VaultMintConditionalTokens => {
-                index_mint_ix(Arc::clone(&connection), &payload_parsed, tx.tx_sig.clone()).await;
-            }
-            InstructionType::AmmSwap => {
-                index_swap_ix(connection, &payload_parsed, tx.tx_sig).await;
-            }
-            InstructionType::AmmDeposit => {
-                let amm_deposit_res = services::liquidity::handle_lp_deposit_tx(
-                    connection,
-                    &payload_parsed,
-                    tx.tx_sig.clone(),
-                );
-
-                let amm_deposit_res_awaited = block_on(amm_deposit_res);
-                match amm_deposit_res_awaited {
-                    Ok(_) => println!(
-                        "handled amm deposit tx: {:?}, {:?}",
-                        payload_parsed.signatures,
-                        payload_parsed.get_main_ix_type()
-                    ),
-                    Err(e) => eprintln!(
-                        "error tracking amm deposit: {:?}. payload: {:?}",
-                        e, payload_parsed
-                    ),
-                }
-            }
-            InstructionType::AmmWithdraw => {
-                let amm_withdrawal_res = services::liquidity::handle_lp_withdrawal_tx(
-                    connection,
+    let results = match ix_type {
+        InstructionType::VaultMintAndAmmSwap => {
+            // both halves may write the same user token accts; those writes are serialized per acct
+            let (mint_res, swap_res) = tokio::join!(
+                services::new_mint::handle_mint_tx(
+                    pool.clone(),
                     &payload_parsed,
-                    tx.tx_sig.clone(),
-                );
-
-                let amm_withdrawal_res_awaited = block_on(amm_withdrawal_res);
-                match amm_withdrawal_res_awaited {
-                    Ok(_) => println!(
-                        "handled amm withdrawal tx: {:?}, {:?}",
-                        payload_parsed.signatures,
-                        payload_parsed.get_main_ix_type()
-                    ),
-                    Err(e) => eprintln!(
-                        "error tracking amm withdrawal: {:?}. payload: {:?}",
-                        e, payload_parsed
-                    ),
-                }
-            }
-            InstructionType::VaultMergeConditionalTokens => {
-                let merge_conditionals_res =
-                    services::merge_conditionals_for_underlying::handle_merge_conditional_tokens_tx(
-                        connection,
-                        &payload_parsed,
-                        tx.tx_sig.clone(),
-                    );
-
-                let merge_conditionals_res_awaited = block_on(merge_conditionals_res);
-                match merge_conditionals_res_awaited {
-                    Ok(_) => println!(
-                        "handled merge conditionals tx: {:?}, {:?}",
-                        payload_parsed.signatures,
-                        payload_parsed.get_main_ix_type()
-                    ),
-                    Err(e) => eprintln!(
-                        "error tracking merge conditionals: {:?}. payload: {:?}",
-                        e, payload_parsed
-                    ),
-                }
-            }
-            InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens => {
-                let redeem_conditionals_res =
-                    services::redeem_conditionals::handle_redeem_conditional_tokens_tx(
-                        connection,
-                        &payload_parsed,
-                        tx.tx_sig.clone(),
-                    );
-
-                let redeem_conditionals_res_awaited = block_on(redeem_conditionals_res);
-                match redeem_conditionals_res_awaited {
-                    Ok(_) => println!(
-                        "handled redeem conditionals tx: {:?}, {:?}",
-                        payload_parsed.signatures,
-                        payload_parsed.get_main_ix_type()
-                    ),
-                    Err(e) => eprintln!(
-                        "error tracking redeem conditionals: {:?}. payload: {:?}",
-                        e, payload_parsed
-                    ),
-                }
-            }
-            x => println!("unhandled ix type: {:?}", x),
-        },
-        None => println!("tx has no ix type we care about"),
+                    transaction_sig.clone()
+                ),
+                services::swaps::handle_swap_tx(pool, &payload_parsed, transaction_sig.clone()),
+            );
+            vec![("new mint", mint_res), ("swap", swap_res)]
+        }
+        InstructionType::VaultMintConditionalTokens => vec![(
+            "new mint",
+            services::new_mint::handle_mint_tx(pool, &payload_parsed, transaction_sig.clone())
+                .await,
+        )],
+        InstructionType::AmmSwap => vec![(
+            "swap",
+            services::swaps::handle_swap_tx(pool, &payload_parsed, transaction_sig.clone()).await,
+        )],
+        InstructionType::AmmDeposit => vec![(
+            "amm deposit",
+            services::liquidity::handle_lp_deposit_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::AmmWithdraw => vec![(
+            "amm withdrawal",
+            services::liquidity::handle_lp_withdrawal_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::OpenbookPlaceOrder => vec![(
+            "place order",
+            services::orders::handle_place_order_tx(pool, &payload_parsed, transaction_sig.clone())
+                .await,
+        )],
+        InstructionType::OpenbookCancelOrder => vec![(
+            "cancel order",
+            services::orders::handle_cancel_order_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::AutocratInitializeProposal => vec![(
+            "initialize proposal",
+            services::proposals::handle_initialize_proposal_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::AutocratFinalizeProposal => vec![(
+            "finalize proposal",
+            services::proposals::handle_finalize_proposal_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::VaultMergeConditionalTokens => vec![(
+            "merge conditionals",
+            services::merge_conditionals_for_underlying::handle_merge_conditional_tokens_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        InstructionType::VaultRedeemConditionalTokensForUnderlyingTokens => vec![(
+            "redeem conditionals",
+            services::redeem_conditionals::handle_redeem_conditional_tokens_tx(
+                pool,
+                &payload_parsed,
+                transaction_sig.clone(),
+            )
+            .await,
+        )],
+        x => {
+            println!("unhandled ix type: {:?}", x);
+            vec![]
+        }
+    };
+
+    let report = IndexingReport {
+        tx_sig: transaction_sig,
+        main_ix_type: Some(ix_type),
+        results,
+        skipped: false,
+    };
+    report.log(&payload_parsed);
+    // the indexers already ran, so a ledger write failure only means the tx may be indexed again
+    if let Err(e) = record_outcome(ledger_pool, &report, indexer_version).await {
+        eprintln!(
+            "error recording indexing outcome for tx {}: {}",
+            report.tx_sig, e
+        );
     }
+    if let Err(e) = update_dead_letter(dead_letter_pool, &report).await {
+        eprintln!("error updating dead letter for tx {}: {}", report.tx_sig, e);
+    }
+    Ok(report)
+}
 
+async fn update_dead_letter(pool: Pool, report: &IndexingReport) -> Result<(), AssetWatcherError> {
+    let Some((_, first_failure)) = report.failures().next() else {
+        if services::dead_letters::clear(pool, report.tx_sig.clone()).await? {
+            println!("tx {} indexed, removed from dead letters", report.tx_sig);
+        }
+        return Ok(());
+    };
+    let entry = services::dead_letters::record_failure(
+        pool,
+        report.tx_sig.clone(),
+        first_failure.kind(),
+        report.error_summary().unwrap_or_default(),
+        report.failures().all(|(_, e)| e.is_retryable()),
+    )
+    .await?;
+    match entry.next_retry_at {
+        Some(at) => println!(
+            "dead lettered tx {} after {} attempts, retrying at {}",
+            entry.tx_sig, entry.attempts, at
+        ),
+        None => eprintln!(
+            "tx {} is stuck after {} attempts: {}",
+            entry.tx_sig, entry.attempts, entry.error
+        ),
+    }
     Ok(())
 }
 
-async fn index_swap_ix(connection: Arc<Object<Manager<PgConnection>>>, payload_parsed: &Payload, transaction_sig: String) {
-    let swap_res = services::swaps::handle_swap_tx(
-        connection,
-        &payload_parsed,
-        transaction_sig,
-    );
+async fn already_indexed(
+    pool: Pool,
+    transaction_sig: &str,
+    indexer_version: i16,
+) -> Result<bool, AssetWatcherError> {
+    let transaction_sig = transaction_sig.to_string();
+    let entries = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            indexed_transactions::table
+                .filter(indexed_transactions::tx_sig.eq(transaction_sig))
+                .filter(indexed_transactions::indexer_version.eq(indexer_version))
+                .filter(indexed_transactions::outcome.ne(IndexOutcome::Failed))
+                .count()
+                .get_result::<i64>(conn)
+        })
+        .await??;
+    Ok(entries > 0)
+}
+
+async fn record_outcome(
+    pool: Pool,
+    report: &IndexingReport,
+    indexer_version: i16,
+) -> Result<(), AssetWatcherError> {
+    let entry = IndexedTransaction {
+        tx_sig: report.tx_sig.clone(),
+        indexer_version,
+        outcome: report.outcome(),
+        error: report.error_summary(),
+        indexed_at: Utc::now(),
+    };
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            diesel::insert_into(indexed_transactions::table)
+                .values(&entry)
+                .on_conflict((
+                    indexed_transactions::tx_sig,
+                    indexed_transactions::indexer_version,
+                ))
+                .do_update()
+                .set(&entry)
+                .execute(conn)
+        })
+        .await??;
+    Ok(())
+}
 
-    let swap_res_awaited = block_on(swap_res);
-    match swap_res_awaited {
+fn log_index_result(label: &str, res: &Result<(), AssetWatcherError>, payload_parsed: &Payload) {
+    match res {
         Ok(_) => println!(
-            "handled swap tx: {:?}, {:?}",
+            "handled {} tx: {:?}, {:?}",
+            label,
             payload_parsed.signatures,
             payload_parsed.get_main_ix_type()
         ),
-        Err(e) => eprintln!(
-            "error tracking swap: {:?}. payload: {:?}",
-            e, payload_parsed
+        Err(e @ AssetWatcherError::MissingMarket(_))
+        | Err(e @ AssetWatcherError::MissingConditionalVault(_))
+        | Err(e @ AssetWatcherError::MissingProposal(_)) => eprintln!(
+            "{} tx references a row that is not indexed yet: {}. signatures: {:?}",
+            label, e, payload_parsed.signatures
         ),
-    }
-}
-async fn index_mint_ix(connection: Arc<Object<Manager<PgConnection>>>, payload_parsed: &Payload, transaction_sig: String) {
-    let mint_handler_res = services::new_mint::handle_mint_tx(
-        Arc::clone(&connection),
-        &payload_parsed,
-        transaction_sig,
-    );
-
-    let mint_handler_res_awaited = block_on(mint_handler_res);
-    match mint_handler_res_awaited {
-        Ok(_) => println!(
-            "handled new mint tx: {:?}, {:?}",
-            payload_parsed.signatures,
-            payload_parsed.get_main_ix_type()
+        Err(e) if e.is_retryable() => eprintln!(
+            "retryable {} error tracking {}: {}. signatures: {:?}",
+            e.kind(),
+            label,
+            e,
+            payload_parsed.signatures
         ),
         Err(e) => eprintln!(
-            "error tracking new mint: {:?}. payload instructions: {:?}",
-            e, payload_parsed.instructions
+            "{} error tracking {}: {}. payload: {:?}",
+            e.kind(),
+            label,
+            e,
+            payload_parsed
         ),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[path = "transactions_insert_test.rs"]
+mod tests;
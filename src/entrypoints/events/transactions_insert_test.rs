@@ -0,0 +1,45 @@
+use super::*;
+
+fn report(results: Vec<(&'static str, Result<(), AssetWatcherError>)>) -> IndexingReport {
+    IndexingReport {
+        tx_sig: "sig".to_string(),
+        main_ix_type: Some(InstructionType::VaultMintAndAmmSwap),
+        results,
+        skipped: false,
+    }
+}
+
+#[test]
+fn test_outcome_without_results_is_unhandled() {
+    let report = IndexingReport::empty("sig".to_string(), None, false);
+
+    assert_eq!(report.outcome(), IndexOutcome::Unhandled);
+    assert!(report.error_summary().is_none());
+}
+
+#[test]
+fn test_outcome_with_every_indexer_succeeding_is_indexed() {
+    let report = report(vec![("new mint", Ok(())), ("swap", Ok(()))]);
+
+    assert!(report.succeeded());
+    assert_eq!(report.outcome(), IndexOutcome::Indexed);
+    assert!(report.error_summary().is_none());
+}
+
+#[test]
+fn test_outcome_with_any_indexer_failing_is_failed() {
+    let report = report(vec![
+        ("new mint", Ok(())),
+        (
+            "swap",
+            Err(AssetWatcherError::PayloadParse("bad swap".to_string())),
+        ),
+    ]);
+
+    assert!(!report.succeeded());
+    assert_eq!(report.outcome(), IndexOutcome::Failed);
+    assert_eq!(
+        report.error_summary().as_deref(),
+        Some("swap: payload parse error: bad swap")
+    );
+}
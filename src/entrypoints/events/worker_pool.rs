@@ -0,0 +1,189 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::FutureExt;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Mutex;
+
+use crate::config::{Config, OverflowPolicy};
+use crate::shutdown::Shutdown;
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/**
+ * A fixed number of workers fed from a bounded queue, so a burst of events waits its turn
+ * instead of spawning a task per event that all compete for the same db connections.
+ * What happens when the queue is full is up to the pool's overflow policy.
+ * Workers stop taking new jobs on shutdown, but a job already running is let finish.
+ */
+#[derive(Clone)]
+pub struct WorkerPool {
+    name: &'static str,
+    sender: mpsc::Sender<Job>,
+    overflow: OverflowPolicy,
+    counters: Arc<Counters>,
+}
+
+struct Counters {
+    workers: usize,
+    capacity: usize,
+    running: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkerPoolStats {
+    pub name: String,
+    pub workers: usize,
+    pub capacity: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub dropped: u64,
+    pub overflow: OverflowPolicy,
+}
+
+impl WorkerPool {
+    pub fn new(
+        name: &'static str,
+        workers: usize,
+        capacity: usize,
+        overflow: OverflowPolicy,
+        shutdown: &Shutdown,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let counters = Arc::new(Counters {
+            workers,
+            capacity,
+            running: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        });
+
+        for _ in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            let counters = Arc::clone(&counters);
+            let worker_shutdown = shutdown.clone();
+            shutdown.spawn(async move {
+                loop {
+                    let job = tokio::select! {
+                        _ = worker_shutdown.cancelled() => return,
+                        job = async { receiver.lock().await.recv().await } => match job {
+                            Some(job) => job,
+                            None => return,
+                        },
+                    };
+                    counters.running.fetch_add(1, Ordering::SeqCst);
+                    // a panicking job should not take the worker down with it
+                    if AssertUnwindSafe(job).catch_unwind().await.is_err() {
+                        eprintln!("job panicked in {} worker pool", name);
+                    }
+                    counters.running.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        }
+
+        WorkerPool {
+            name,
+            sender,
+            overflow,
+            counters,
+        }
+    }
+
+    /**
+     * Queues the job, waiting for room or dropping it when the queue is full per the overflow policy.
+     * Returns whether the job was queued, so the caller can log what a dropped job was for.
+     */
+    pub async fn submit<F>(&self, job: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let job: Job = Box::pin(job);
+        let res = match self.overflow {
+            OverflowPolicy::Block => self.sender.send(job).await.map(|_| true).map_err(|_| ()),
+            OverflowPolicy::Drop => match self.sender.try_send(job) {
+                Ok(()) => Ok(true),
+                Err(TrySendError::Full(_)) => {
+                    let dropped = self.counters.dropped.fetch_add(1, Ordering::SeqCst) + 1;
+                    eprintln!(
+                        "{} queue is full, dropped job ({} dropped so far)",
+                        self.name, dropped
+                    );
+                    Ok(false)
+                }
+                Err(TrySendError::Closed(_)) => Err(()),
+            },
+        };
+        match res {
+            Ok(queued) => queued,
+            Err(()) => {
+                println!("{} workers have stopped, not queueing job", self.name);
+                false
+            }
+        }
+    }
+
+    pub fn stats(&self) -> WorkerPoolStats {
+        WorkerPoolStats {
+            name: self.name.to_string(),
+            workers: self.counters.workers,
+            capacity: self.counters.capacity,
+            queued: self.counters.capacity - self.sender.capacity(),
+            running: self.counters.running.load(Ordering::SeqCst),
+            dropped: self.counters.dropped.load(Ordering::SeqCst),
+            overflow: self.overflow,
+        }
+    }
+}
+
+/**
+ * One worker pool per kind of event, so a flood of one kind cannot starve the others.
+ * Outbox events always wait for room: they stay claimed in the outbox until handled,
+ * so blocking just slows down claiming. Account updates follow the configured policy.
+ */
+#[derive(Clone)]
+pub struct EventWorkers {
+    pub transactions: WorkerPool,
+    pub token_accts: WorkerPool,
+    pub account_updates: WorkerPool,
+}
+
+impl EventWorkers {
+    pub fn new(config: &Config, shutdown: &Shutdown) -> Self {
+        EventWorkers {
+            transactions: WorkerPool::new(
+                "transactions",
+                config.transaction_workers,
+                config.event_queue_capacity,
+                OverflowPolicy::Block,
+                shutdown,
+            ),
+            token_accts: WorkerPool::new(
+                "token_accts",
+                config.token_acct_workers,
+                config.event_queue_capacity,
+                OverflowPolicy::Block,
+                shutdown,
+            ),
+            account_updates: WorkerPool::new(
+                "account_updates",
+                config.account_update_workers,
+                config.event_queue_capacity,
+                config.account_update_overflow,
+                shutdown,
+            ),
+        }
+    }
+
+    pub fn stats(&self) -> Vec<WorkerPoolStats> {
+        vec![
+            self.transactions.stats(),
+            self.token_accts.stats(),
+            self.account_updates.stats(),
+        ]
+    }
+}
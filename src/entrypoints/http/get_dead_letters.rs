@@ -0,0 +1,49 @@
+use deadpool_diesel::postgres::Pool;
+use serde::Deserialize;
+use warp::http::StatusCode;
+use warp::Reply;
+
+use crate::entities::token_accts::WatchTokenBalanceResponse;
+use crate::services::dead_letters;
+
+use super::post_watch_token_acct::status_code_for_error;
+
+#[derive(Deserialize)]
+pub struct DeadLettersQuery {
+    /// only txs that are no longer retried
+    #[serde(default)]
+    pub stuck: bool,
+}
+
+/// Transactions whose indexing failed, with their error and retry schedule.
+pub async fn handler(
+    query: DeadLettersQuery,
+    reply_with_status: warp::reply::WithStatus<&'static str>,
+    pool: Pool,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let response = reply_with_status.into_response();
+    if !response.status().is_success() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse {
+                message: "unsuccessful response status".to_string(),
+            }),
+            response.status(),
+        ));
+    }
+
+    match dead_letters::list(pool, query.stuck).await {
+        Ok(entries) => Ok(warp::reply::with_status(
+            warp::reply::json(&entries),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("error handling dead letters request: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&WatchTokenBalanceResponse {
+                    message: e.to_string(),
+                }),
+                status_code_for_error(&e),
+            ))
+        }
+    }
+}
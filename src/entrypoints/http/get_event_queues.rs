@@ -0,0 +1,25 @@
+use warp::http::StatusCode;
+use warp::Reply;
+
+use crate::entities::token_accts::WatchTokenBalanceResponse;
+use crate::entrypoints::events::worker_pool::EventWorkers;
+
+pub async fn handler(
+    reply_with_status: warp::reply::WithStatus<&'static str>,
+    workers: EventWorkers,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let response = reply_with_status.into_response();
+    if !response.status().is_success() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse {
+                message: "unsuccessful response status".to_string(),
+            }),
+            response.status(),
+        ));
+    }
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&workers.stats()),
+        StatusCode::OK,
+    ))
+}
@@ -0,0 +1,69 @@
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use warp::http::StatusCode;
+use warp::Reply;
+
+use crate::entities::token_accts::{token_accts, TokenAcct, WatchTokenBalanceResponse};
+use crate::entities::watch_targets::MintHolder;
+use crate::errors::AssetWatcherError;
+
+use super::post_watch_token_acct::status_code_for_error;
+
+/// Every token acct holding a non zero balance of the mint, largest first.
+pub async fn handler(
+    mint_acct: String,
+    reply_with_status: warp::reply::WithStatus<&'static str>,
+    pool: Pool,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let response = reply_with_status.into_response();
+    if !response.status().is_success() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse {
+                message: "unsuccessful response status".to_string(),
+            }),
+            response.status(),
+        ));
+    }
+
+    match get_mint_holders(pool, mint_acct).await {
+        Ok(holders) => Ok(warp::reply::with_status(
+            warp::reply::json(&holders),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("error handling mint holders request: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&WatchTokenBalanceResponse {
+                    message: e.to_string(),
+                }),
+                status_code_for_error(&e),
+            ))
+        }
+    }
+}
+
+async fn get_mint_holders(
+    pool: Pool,
+    mint_acct: String,
+) -> Result<Vec<MintHolder>, AssetWatcherError> {
+    let holders: Vec<TokenAcct> = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            token_accts::table
+                .filter(token_accts::mint_acct.eq(mint_acct))
+                .filter(token_accts::amount.gt(bigdecimal::BigDecimal::from(0)))
+                .order(token_accts::amount.desc())
+                .load::<TokenAcct>(db)
+        })
+        .await??;
+
+    Ok(holders
+        .into_iter()
+        .map(|record| MintHolder {
+            token_acct: record.token_acct,
+            owner_acct: record.owner_acct,
+            amount: record.amount.to_string(),
+        })
+        .collect())
+}
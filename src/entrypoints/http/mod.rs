@@ -1,2 +1,7 @@
+pub mod get_dead_letters;
+pub mod get_event_queues;
+pub mod get_mint_holders;
+pub mod get_subscriptions;
+pub mod post_watch_target;
 pub mod post_watch_token_acct;
 pub mod routes;
@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+use warp::http::StatusCode;
+use warp::Reply;
+
+use crate::entities::token_accts::{TokenAcctStatus, WatchTokenBalanceResponse};
+use crate::entities::watch_targets::{watch_targets, WatchTarget, WatchTargetType};
+use crate::entrypoints::events::subscriptions::SubscriptionManager;
+use crate::errors::AssetWatcherError;
+
+use super::post_watch_token_acct::status_code_for_error;
+
+pub async fn handler(
+    reply_with_status: warp::reply::WithStatus<&'static str>,
+    target_acct: String,
+    target_type: WatchTargetType,
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let response = reply_with_status.into_response();
+    if !response.status().is_success() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse {
+                message: "unsuccessful response status".to_string(),
+            }),
+            response.status(),
+        ));
+    }
+
+    reply_with_result(watch_target(pool, subscriptions, target_type, target_acct).await)
+}
+
+pub async fn delete_handler(
+    reply_with_status: warp::reply::WithStatus<&'static str>,
+    target_acct: String,
+    target_type: WatchTargetType,
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let response = reply_with_status.into_response();
+    if !response.status().is_success() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse {
+                message: "unsuccessful response status".to_string(),
+            }),
+            response.status(),
+        ));
+    }
+
+    reply_with_result(unwatch_target(pool, subscriptions, target_type, target_acct).await)
+}
+
+fn reply_with_result(
+    result: Result<String, AssetWatcherError>,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    match result {
+        Ok(message) => Ok(warp::reply::with_status(
+            warp::reply::json(&WatchTokenBalanceResponse { message }),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("error handling watch target request: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&WatchTokenBalanceResponse {
+                    message: e.to_string(),
+                }),
+                status_code_for_error(&e),
+            ))
+        }
+    }
+}
+
+async fn watch_target(
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+    target_type: WatchTargetType,
+    target_acct: String,
+) -> Result<String, AssetWatcherError> {
+    Pubkey::from_str(&target_acct)?;
+
+    let new_target = WatchTarget {
+        target_acct: target_acct.clone(),
+        target_type,
+        status: TokenAcctStatus::Watching,
+        created_at: Utc::now(),
+        updated_at: Some(Utc::now()),
+    };
+    let target: WatchTarget = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            diesel::insert_into(watch_targets::table)
+                .values(&new_target)
+                .on_conflict((watch_targets::target_acct, watch_targets::target_type))
+                .do_update()
+                .set((
+                    watch_targets::status.eq(TokenAcctStatus::Watching),
+                    watch_targets::updated_at.eq(Utc::now()),
+                ))
+                .get_result(db)
+        })
+        .await??;
+
+    if subscriptions.watch_target(&target)? {
+        Ok(format!(
+            "watching token accts for {}: {}",
+            target_type, target_acct
+        ))
+    } else {
+        Ok(format!(
+            "already watching token accts for {}: {}",
+            target_type, target_acct
+        ))
+    }
+}
+
+async fn unwatch_target(
+    pool: Pool,
+    subscriptions: SubscriptionManager,
+    target_type: WatchTargetType,
+    target_acct: String,
+) -> Result<String, AssetWatcherError> {
+    let target_acct_for_update = target_acct.clone();
+    let updated = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            diesel::update(
+                watch_targets::table.filter(
+                    watch_targets::target_acct
+                        .eq(target_acct_for_update)
+                        .and(watch_targets::target_type.eq(target_type)),
+                ),
+            )
+            .set((
+                watch_targets::status.eq(TokenAcctStatus::Disabled),
+                watch_targets::updated_at.eq(Utc::now()),
+            ))
+            .execute(db)
+        })
+        .await??;
+    if updated == 0 {
+        return Err(AssetWatcherError::MissingTokenRecord(target_acct));
+    }
+
+    subscriptions.unwatch_target(target_type, &target_acct)?;
+    Ok(format!(
+        "stopped watching token accts for {}: {}",
+        target_type, target_acct
+    ))
+}
@@ -1,26 +1,32 @@
-use std::{env, sync::Arc};
+use std::sync::Arc;
 
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::PgConnection;
-use solana_client::nonblocking::pubsub_client::PubsubClient;
+use deadpool_diesel::postgres::Pool;
 use tokio::sync::Mutex;
 use warp::Filter;
 
-use crate::{entities::token_accts::WatchTokenBalancePayload, services::auth::AuthClient};
+use crate::{
+    config::Config,
+    entities::token_accts::WatchTokenBalancePayload,
+    entities::watch_targets::{WatchMintPayload, WatchOwnerPayload, WatchTargetType},
+    entrypoints::events::subscriptions::SubscriptionManager,
+    entrypoints::events::worker_pool::EventWorkers,
+    services::auth::AuthClient,
+    shutdown::Shutdown,
+};
 
-use super::post_watch_token_acct;
+use super::{
+    get_dead_letters, get_event_queues, get_mint_holders, get_subscriptions, post_watch_target,
+    post_watch_token_acct,
+};
 
-pub async fn listen_and_serve(db: Arc<Object<Manager<PgConnection>>>) {
-    let port = match env::var("PORT")
-        .unwrap_or("8080".to_string())
-        .parse::<u16>()
-    {
-        Ok(port) => port,
-        Err(_) => 8080,
-    };
-    let auth_service_url = env::var("AUTH_SERVICE_URL").expect("AUTH_SERVICE_URL must be set");
-    let auth_client = Arc::new(Mutex::new(AuthClient::new(&auth_service_url)));
+pub async fn listen_and_serve(
+    pool: Pool,
+    config: Arc<Config>,
+    subscriptions: SubscriptionManager,
+    workers: EventWorkers,
+    shutdown: Shutdown,
+) {
+    let auth_client = Arc::new(Mutex::new(AuthClient::new(&config.auth_service_url)));
 
     let auth_filter = warp::any()
         .and(warp::header::<String>("authorization"))
@@ -29,13 +35,80 @@ pub async fn listen_and_serve(db: Arc<Object<Manager<PgConnection>>>) {
 
     let watch_balance_route = warp::post()
         .and(warp::path("watch-token-balance"))
-        .and(auth_filter)
-        .and(watch_token_json_body())
-        .and(with_db(db))
+        .and(auth_filter.clone())
+        .and(watch_token_json_body(config.body_size_limit_bytes))
+        .and(with_db(pool.clone()))
+        .and(with_config(Arc::clone(&config)))
+        .and(with_subscriptions(subscriptions.clone()))
         .and_then(post_watch_token_acct::handler);
 
-    let cors = warp::cors()
-        .allow_any_origin()
+    let watch_owner_route = warp::post()
+        .and(warp::path("watch-owner"))
+        .and(auth_filter.clone())
+        .and(owner_acct_body(config.body_size_limit_bytes))
+        .and(with_target_type(WatchTargetType::Owner))
+        .and(with_db(pool.clone()))
+        .and(with_subscriptions(subscriptions.clone()))
+        .and_then(post_watch_target::handler);
+
+    let unwatch_owner_route = warp::delete()
+        .and(warp::path("watch-owner"))
+        .and(auth_filter.clone())
+        .and(owner_acct_body(config.body_size_limit_bytes))
+        .and(with_target_type(WatchTargetType::Owner))
+        .and(with_db(pool.clone()))
+        .and(with_subscriptions(subscriptions.clone()))
+        .and_then(post_watch_target::delete_handler);
+
+    let watch_mint_route = warp::post()
+        .and(warp::path("watch-mint"))
+        .and(auth_filter.clone())
+        .and(mint_acct_body(config.body_size_limit_bytes))
+        .and(with_target_type(WatchTargetType::Mint))
+        .and(with_db(pool.clone()))
+        .and(with_subscriptions(subscriptions.clone()))
+        .and_then(post_watch_target::handler);
+
+    let unwatch_mint_route = warp::delete()
+        .and(warp::path("watch-mint"))
+        .and(auth_filter.clone())
+        .and(mint_acct_body(config.body_size_limit_bytes))
+        .and(with_target_type(WatchTargetType::Mint))
+        .and(with_db(pool.clone()))
+        .and(with_subscriptions(subscriptions.clone()))
+        .and_then(post_watch_target::delete_handler);
+
+    let mint_holders_route = warp::get()
+        .and(warp::path!("mints" / String / "holders"))
+        .and(auth_filter.clone())
+        .and(with_db(pool.clone()))
+        .and_then(get_mint_holders::handler);
+
+    let subscriptions_route = warp::get()
+        .and(warp::path("subscriptions"))
+        .and(auth_filter.clone())
+        .and(with_subscriptions(subscriptions))
+        .and_then(get_subscriptions::handler);
+
+    let event_queues_route = warp::get()
+        .and(warp::path("event-queues"))
+        .and(auth_filter.clone())
+        .and(with_workers(workers))
+        .and_then(get_event_queues::handler);
+
+    let dead_letters_route = warp::get()
+        .and(warp::path("dead-letters"))
+        .and(warp::query::<get_dead_letters::DeadLettersQuery>())
+        .and(auth_filter)
+        .and(with_db(pool.clone()))
+        .and_then(get_dead_letters::handler);
+
+    let cors = if config.cors_allowed_origins.is_empty() {
+        warp::cors().allow_any_origin()
+    } else {
+        warp::cors().allow_origins(config.cors_allowed_origins.iter().map(String::as_str))
+    };
+    let cors = cors
         .allow_headers(vec![
             "User-Agent",
             "Sec-Fetch-Mode",
@@ -49,16 +122,49 @@ pub async fn listen_and_serve(db: Arc<Object<Manager<PgConnection>>>) {
             "Sec-Ch-Ua-Mobile",
             "Sec-Ch-Ua-Platform",
         ])
-        .allow_methods(vec!["POST", "GET", "OPTIONS"]);
+        .allow_methods(vec!["POST", "GET", "DELETE", "OPTIONS"]);
 
-    let routes = watch_balance_route.with(cors);
+    let routes = watch_balance_route
+        .or(watch_owner_route)
+        .or(unwatch_owner_route)
+        .or(watch_mint_route)
+        .or(unwatch_mint_route)
+        .or(mint_holders_route)
+        .or(subscriptions_route)
+        .or(event_queues_route)
+        .or(dead_letters_route)
+        .with(cors);
 
-    warp::serve(routes).run(([0, 0, 0, 0], port)).await
+    let (_, server) =
+        warp::serve(routes).bind_with_graceful_shutdown(([0, 0, 0, 0], config.port), async move {
+            shutdown.cancelled().await;
+            println!("shutdown requested, draining http connections");
+        });
+    server.await
 }
 
 fn watch_token_json_body(
+    body_size_limit_bytes: u64,
 ) -> impl Filter<Extract = (WatchTokenBalancePayload,), Error = warp::Rejection> + Clone {
-    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+    json_body(body_size_limit_bytes)
+}
+
+fn owner_acct_body(
+    body_size_limit_bytes: u64,
+) -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    json_body(body_size_limit_bytes).map(|payload: WatchOwnerPayload| payload.owner_acct)
+}
+
+fn mint_acct_body(
+    body_size_limit_bytes: u64,
+) -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    json_body(body_size_limit_bytes).map(|payload: WatchMintPayload| payload.mint_acct)
+}
+
+fn json_body<T: serde::de::DeserializeOwned + Send>(
+    body_size_limit_bytes: u64,
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(body_size_limit_bytes).and(warp::body::json())
 }
 
 fn with_auth_client(
@@ -66,23 +172,37 @@ fn with_auth_client(
 ) -> impl Filter<Extract = (Arc<Mutex<AuthClient>>,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || auth_client.clone())
 }
-fn with_db(
-    db: Arc<Object<Manager<PgConnection>>>,
-) -> impl Filter<Extract = (Arc<Object<Manager<PgConnection>>>,), Error = std::convert::Infallible> + Clone
-{
-    warp::any().map(move || db.clone())
+fn with_db(pool: Pool) -> impl Filter<Extract = (Pool,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || pool.clone())
 }
-fn with_rpc_pub_sub(
-    rpc_pub_sub: Arc<PubsubClient>,
-) -> impl Filter<Extract = (Arc<PubsubClient>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || rpc_pub_sub.clone())
+fn with_config(
+    config: Arc<Config>,
+) -> impl Filter<Extract = (Arc<Config>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || config.clone())
+}
+fn with_target_type(
+    target_type: WatchTargetType,
+) -> impl Filter<Extract = (WatchTargetType,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || target_type)
+}
+
+fn with_subscriptions(
+    subscriptions: SubscriptionManager,
+) -> impl Filter<Extract = (SubscriptionManager,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || subscriptions.clone())
+}
+
+fn with_workers(
+    workers: EventWorkers,
+) -> impl Filter<Extract = (EventWorkers,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || workers.clone())
 }
 
 async fn validate_token(
     token: String,
     auth_client: Arc<Mutex<AuthClient>>,
 ) -> Result<warp::reply::WithStatus<&'static str>, warp::Rejection> {
-    let auth_client = auth_client.lock().await;
+    let _auth_client = auth_client.lock().await;
 
     // Check if the token starts with "Bearer "
     if !token.starts_with("Bearer ") {
@@ -0,0 +1,195 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::Config;
+use crate::entities::reconciliation_runs::{reconciliation_runs, NewReconciliationRun};
+use crate::entities::token_accts::{token_accts, TokenAcct, TokenAcctStatus};
+use crate::errors::AssetWatcherError;
+use crate::services::transactions::handle_token_acct_balance_tx;
+use crate::shutdown::Shutdown;
+
+// getMultipleAccounts accepts at most 100 pubkeys per call
+const BATCH_SIZE: i64 = 100;
+
+/**
+ * Periodically compares every Watching and Enabled token acct against chain state and writes a
+ * corrective balance for any that drifted, e.g. because an update was missed while a subscription
+ * was down. An acct closed on chain is corrected to zero, which is also the only way a closed acct
+ * found through a program subscription is seen, since closing it sends no notification there.
+ */
+pub async fn run_job(pool: Pool, config: Arc<Config>, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(config.reconciliation_interval);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                println!("shutdown requested, stopping balance reconciliation");
+                return;
+            }
+            _ = interval.tick() => {}
+        }
+        match reconcile_balances(pool.clone(), &config, &shutdown).await {
+            Ok(run) => println!(
+                "balance reconciliation checked {} accts, corrected {} ({:?}), {} missing on chain, {} unreadable, {} failed batches",
+                run.accts_checked,
+                run.accts_corrected,
+                run.corrected_accts,
+                run.accts_missing,
+                run.accts_unreadable,
+                run.failed_batches
+            ),
+            Err(e) => eprintln!("error running balance reconciliation: {}", e),
+        }
+    }
+}
+
+async fn reconcile_balances(
+    pool: Pool,
+    config: &Config,
+    shutdown: &Shutdown,
+) -> Result<NewReconciliationRun, AssetWatcherError> {
+    let rpc_client =
+        RpcClient::new_with_commitment(config.rpc_endpoint_http.clone(), config.commitment);
+    let mut run = NewReconciliationRun {
+        started_at: Utc::now(),
+        finished_at: Utc::now(),
+        accts_checked: 0,
+        accts_corrected: 0,
+        accts_missing: 0,
+        accts_unreadable: 0,
+        failed_batches: 0,
+        corrected_accts: vec![],
+    };
+
+    // keyset pagination so accts changing status mid run do not shift the pages
+    let mut last_token_acct = String::new();
+    loop {
+        if shutdown.is_triggered() {
+            break;
+        }
+        let batch = load_tracked_batch(pool.clone(), last_token_acct.clone()).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        last_token_acct = last.token_acct.clone();
+
+        if let Err(e) = reconcile_batch(pool.clone(), &rpc_client, batch, &mut run).await {
+            eprintln!(
+                "error reconciling batch ending at {}: {}",
+                last_token_acct, e
+            );
+            run.failed_batches += 1;
+        }
+    }
+
+    run.finished_at = Utc::now();
+    let run_to_insert = run.clone();
+    pool.get()
+        .await?
+        .interact(move |conn| {
+            diesel::insert_into(reconciliation_runs::table)
+                .values(&run_to_insert)
+                .execute(conn)
+        })
+        .await??;
+    Ok(run)
+}
+
+async fn reconcile_batch(
+    pool: Pool,
+    rpc_client: &RpcClient,
+    batch: Vec<TokenAcct>,
+    run: &mut NewReconciliationRun,
+) -> Result<(), AssetWatcherError> {
+    let pubkeys = batch
+        .iter()
+        .map(|record| Pubkey::from_str(&record.token_acct))
+        .collect::<Result<Vec<Pubkey>, _>>()?;
+    let response = rpc_client
+        .get_multiple_accounts_with_commitment(&pubkeys, rpc_client.commitment())
+        .await?;
+    let slot = BigDecimal::from(response.context.slot);
+
+    for (record, account) in batch.into_iter().zip(response.value) {
+        run.accts_checked += 1;
+        let (on_chain_balance, mint_acct, owner_acct) = match account {
+            // a closed acct holds nothing, and keeps the mint and owner it was recorded with
+            None => {
+                run.accts_missing += 1;
+                (
+                    BigDecimal::zero(),
+                    record.mint_acct.clone(),
+                    record.owner_acct.clone(),
+                )
+            }
+            Some(account) => {
+                let token_account = if account.owner == spl_token::id() {
+                    spl_token::state::Account::unpack(&account.data).ok()
+                } else {
+                    None
+                };
+                let Some(token_account) = token_account else {
+                    eprintln!(
+                        "skipping token acct {} that is not an spl token account on chain",
+                        record.token_acct
+                    );
+                    run.accts_unreadable += 1;
+                    continue;
+                };
+                (
+                    BigDecimal::from(token_account.amount),
+                    token_account.mint.to_string(),
+                    token_account.owner.to_string(),
+                )
+            }
+        };
+        if on_chain_balance == record.amount {
+            continue;
+        }
+
+        println!(
+            "correcting drifted balance for {}: {} in db, {} on chain",
+            record.token_acct, record.amount, on_chain_balance
+        );
+        handle_token_acct_balance_tx(
+            pool.clone(),
+            record.token_acct.clone(),
+            on_chain_balance,
+            None,
+            slot.clone(),
+            mint_acct,
+            owner_acct,
+        )
+        .await?;
+        run.accts_corrected += 1;
+        run.corrected_accts.push(record.token_acct);
+    }
+    Ok(())
+}
+
+/// The next page of token accts we keep balances for, i.e. every acct that is not Disabled.
+async fn load_tracked_batch(
+    pool: Pool,
+    after_token_acct: String,
+) -> Result<Vec<TokenAcct>, AssetWatcherError> {
+    let batch = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            token_accts::table
+                .filter(token_accts::status.ne(TokenAcctStatus::Disabled))
+                .filter(token_accts::token_acct.gt(after_token_acct))
+                .order(token_accts::token_acct.asc())
+                .limit(BATCH_SIZE)
+                .load::<TokenAcct>(conn)
+        })
+        .await??;
+    Ok(batch)
+}
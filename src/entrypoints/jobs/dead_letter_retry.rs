@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use deadpool_diesel::postgres::Pool;
+
+use crate::config::Config;
+use crate::entrypoints::events::transactions_insert::index_tx_record;
+use crate::errors::AssetWatcherError;
+use crate::services::dead_letters;
+use crate::shutdown::Shutdown;
+
+const RETRY_BATCH_SIZE: i64 = 50;
+
+/**
+ * Periodically indexes dead lettered txs whose next retry is due.
+ * Indexing updates the dead letter itself: a tx that succeeds is removed and one that fails
+ * again is rescheduled with a longer backoff.
+ */
+pub async fn run_job(pool: Pool, config: Arc<Config>, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(config.dead_letter_retry_interval);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                println!("shutdown requested, stopping dead letter retries");
+                return;
+            }
+            _ = interval.tick() => {}
+        }
+        match retry_due(pool.clone(), &shutdown).await {
+            Ok((0, _)) => {}
+            Ok((retried, recovered)) => println!(
+                "retried {} dead lettered txs, {} recovered",
+                retried, recovered
+            ),
+            Err(e) => eprintln!("error retrying dead lettered txs: {}", e),
+        }
+    }
+}
+
+async fn retry_due(pool: Pool, shutdown: &Shutdown) -> Result<(usize, usize), AssetWatcherError> {
+    let due = dead_letters::load_due(pool.clone(), RETRY_BATCH_SIZE).await?;
+    let mut retried = 0;
+    let mut recovered = 0;
+    for transaction in due {
+        if shutdown.is_triggered() {
+            break;
+        }
+        retried += 1;
+        let transaction_sig = transaction.tx_sig.clone();
+        match index_tx_record(transaction, pool.clone(), false).await {
+            // indexed successfully since it was dead lettered, e.g. by the backfill
+            Ok(report) if report.skipped => {
+                dead_letters::clear(pool.clone(), transaction_sig).await?;
+                recovered += 1;
+            }
+            Ok(report) if report.succeeded() => recovered += 1,
+            Ok(_) => {}
+            // the tx never reached the indexers, so count it as another failed attempt
+            Err(e) => {
+                dead_letters::record_failure(
+                    pool.clone(),
+                    transaction_sig,
+                    e.kind(),
+                    e.to_string(),
+                    e.is_retryable(),
+                )
+                .await?;
+            }
+        }
+    }
+    Ok((retried, recovered))
+}
@@ -1 +1,3 @@
+pub mod balance_reconciliation;
+pub mod dead_letter_retry;
 pub mod transaction_indexing;
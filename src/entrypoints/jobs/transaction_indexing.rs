@@ -1,49 +1,224 @@
 use std::sync::Arc;
 
-use chrono::{Duration, NaiveDateTime, Utc};
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::{ExpressionMethods, PgConnection};
-
-use crate::entities::transactions::transactions;
-use crate::entities::transactions::transactions::main_ix_type;
-use crate::entities::transactions::{transactions::block_time, Transaction};
-use crate::entrypoints::events;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Duration, Utc};
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
 
+use crate::config::{BackfillConfig, Config};
+use crate::entities::backfill_checkpoints::{backfill_checkpoints, BackfillCheckpoint};
+use crate::entities::transactions::{transactions, Transaction};
+use crate::entrypoints::events;
+use crate::errors::AssetWatcherError;
+use crate::services::dead_letters;
+use crate::shutdown::Shutdown;
+
+const JOB_NAME: &str = "transaction_indexing";
+
+/**
+ * Indexes the configured range of transactions a page at a time, in (slot, tx_sig) order.
+ * The position is checkpointed after every page and on shutdown, and an unfinished checkpoint
+ * with the same range and filter is resumed on the next start.
+ * A tx that cannot be indexed is counted, dead lettered and skipped; only db errors while
+ * paging or checkpointing stop the backfill.
+ */
 pub async fn run_job(
-    pg_connection: Arc<Object<Manager<PgConnection>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let thirty_days_ago = Utc::now().naive_utc() - Duration::days(30);
-
-    // Run the query
-    let transactions =
-        get_recent_transactions_with_main_ix_type(thirty_days_ago, Arc::clone(&pg_connection))
-            .await?;
-
-    // Process each transaction
-    for transaction in transactions {
-        let pg_clone = Arc::clone(&pg_connection);
-        events::transactions_insert::index_tx_record(transaction, pg_clone).await?;
+    pool: Pool,
+    config: Arc<Config>,
+    shutdown: Shutdown,
+) -> Result<(), AssetWatcherError> {
+    let backfill = &config.backfill;
+    let mut checkpoint = load_or_start_checkpoint(pool.clone(), backfill).await?;
+    // only the window is relative to now; explicit bounds are used as configured
+    let window_start = (backfill.start_slot.is_none() && backfill.start_time.is_none())
+        .then(|| Utc::now() - Duration::days(backfill.window_days));
+
+    'pages: loop {
+        let cursor = checkpoint
+            .last_slot
+            .clone()
+            .zip(checkpoint.last_tx_sig.clone());
+        let page = load_page(pool.clone(), backfill.clone(), window_start, cursor).await?;
+        if page.is_empty() {
+            break;
+        }
+
+        for transaction in page {
+            if shutdown.is_triggered() {
+                save_checkpoint(pool.clone(), checkpoint.clone()).await?;
+                println!(
+                    "shutdown requested, stopping transaction backfill at slot {:?}",
+                    checkpoint.last_slot
+                );
+                break 'pages;
+            }
+            checkpoint.last_slot = Some(transaction.slot.clone());
+            checkpoint.last_tx_sig = Some(transaction.tx_sig.clone());
+            index_transaction(pool.clone(), transaction, &config, &mut checkpoint).await;
+        }
+        save_checkpoint(pool.clone(), checkpoint.clone()).await?;
     }
+
+    if !shutdown.is_triggered() {
+        checkpoint.completed_at = Some(Utc::now());
+        save_checkpoint(pool.clone(), checkpoint.clone()).await?;
+    }
+    println!(
+        "transaction backfill indexed {} txs, {} failed, skipped {} already indexed",
+        checkpoint.indexed, checkpoint.failed, checkpoint.skipped
+    );
     Ok(())
 }
 
-async fn get_recent_transactions_with_main_ix_type(
-    thirty_days_ago: NaiveDateTime,
-    connection: Arc<Object<Manager<PgConnection>>>,
-) -> Result<Vec<Transaction>, Box<dyn std::error::Error>> {
-    let res = connection
+async fn index_transaction(
+    pool: Pool,
+    transaction: Transaction,
+    config: &Config,
+    checkpoint: &mut BackfillCheckpoint,
+) {
+    let transaction_sig = transaction.tx_sig.clone();
+    match events::transactions_insert::index_tx_record(
+        transaction,
+        pool.clone(),
+        config.force_reindex,
+    )
+    .await
+    {
+        Ok(report) if report.skipped => checkpoint.skipped += 1,
+        Ok(report) => {
+            checkpoint.indexed += 1;
+            // failed indexers are already dead lettered by index_tx_record
+            if !report.succeeded() {
+                checkpoint.failed += 1;
+            }
+        }
+        Err(e) => {
+            checkpoint.failed += 1;
+            eprintln!(
+                "error indexing tx {} during backfill: {}",
+                transaction_sig, e
+            );
+            if let Err(e) = dead_letters::record_failure(
+                pool,
+                transaction_sig.clone(),
+                e.kind(),
+                e.to_string(),
+                e.is_retryable(),
+            )
+            .await
+            {
+                eprintln!("error dead lettering tx {}: {}", transaction_sig, e);
+            }
+        }
+    }
+}
+
+/// The unfinished checkpoint for the same range and filter, or a fresh one.
+async fn load_or_start_checkpoint(
+    pool: Pool,
+    backfill: &BackfillConfig,
+) -> Result<BackfillCheckpoint, AssetWatcherError> {
+    let params = backfill.fingerprint();
+    let existing = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            backfill_checkpoints::table
+                .find(JOB_NAME)
+                .first::<BackfillCheckpoint>(conn)
+                .optional()
+        })
+        .await??;
+
+    if let Some(checkpoint) = existing {
+        if checkpoint.completed_at.is_none() && checkpoint.params == params {
+            println!(
+                "resuming transaction backfill from slot {:?}, started at {}",
+                checkpoint.last_slot, checkpoint.started_at
+            );
+            return Ok(checkpoint);
+        }
+    }
+
+    let now = Utc::now();
+    let checkpoint = BackfillCheckpoint {
+        job: JOB_NAME.to_string(),
+        params,
+        last_slot: None,
+        last_tx_sig: None,
+        indexed: 0,
+        failed: 0,
+        skipped: 0,
+        started_at: now,
+        updated_at: now,
+        completed_at: None,
+    };
+    println!("starting transaction backfill: {}", checkpoint.params);
+    save_checkpoint(pool, checkpoint.clone()).await?;
+    Ok(checkpoint)
+}
+
+async fn save_checkpoint(
+    pool: Pool,
+    mut checkpoint: BackfillCheckpoint,
+) -> Result<(), AssetWatcherError> {
+    checkpoint.updated_at = Utc::now();
+    pool.get()
+        .await?
         .interact(move |conn| {
-            transactions::table
-                .filter(
-                    main_ix_type
-                        .is_not_null()
-                        .and(block_time.ge(thirty_days_ago)),
-                )
-                .load::<Transaction>(conn)
+            diesel::insert_into(backfill_checkpoints::table)
+                .values(&checkpoint)
+                .on_conflict(backfill_checkpoints::job)
+                .do_update()
+                .set(&checkpoint)
+                .execute(conn)
         })
-        .await?;
+        .await??;
+    Ok(())
+}
 
-    Ok(res?)
+async fn load_page(
+    pool: Pool,
+    backfill: BackfillConfig,
+    window_start: Option<DateTime<Utc>>,
+    cursor: Option<(BigDecimal, String)>,
+) -> Result<Vec<Transaction>, AssetWatcherError> {
+    let page = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            let mut query = transactions::table
+                .filter(transactions::main_ix_type.is_not_null())
+                .into_boxed();
+            if !backfill.ix_types.is_empty() {
+                query = query.filter(transactions::main_ix_type.eq_any(backfill.ix_types));
+            }
+            if let Some(start_slot) = backfill.start_slot {
+                query = query.filter(transactions::slot.ge(BigDecimal::from(start_slot)));
+            }
+            if let Some(end_slot) = backfill.end_slot {
+                query = query.filter(transactions::slot.le(BigDecimal::from(end_slot)));
+            }
+            if let Some(start_time) = backfill.start_time.or(window_start) {
+                query = query.filter(transactions::block_time.ge(start_time));
+            }
+            if let Some(end_time) = backfill.end_time {
+                query = query.filter(transactions::block_time.lt(end_time));
+            }
+            if let Some((last_slot, last_tx_sig)) = cursor {
+                query = query.filter(
+                    transactions::slot
+                        .gt(last_slot.clone())
+                        .or(transactions::slot
+                            .eq(last_slot)
+                            .and(transactions::tx_sig.gt(last_tx_sig))),
+                );
+            }
+            query
+                .order((transactions::slot.asc(), transactions::tx_sig.asc()))
+                .limit(backfill.page_size)
+                .load::<Transaction>(conn)
+        })
+        .await??;
+    Ok(page)
 }
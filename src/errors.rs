@@ -0,0 +1,140 @@
+use std::fmt;
+
+use solana_client::client_error::ClientError;
+use solana_client::pubsub_client::PubsubClientError;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::ParsePubkeyError;
+
+/**
+ * Errors surfaced by services, adapters and entrypoints.
+ * Callers match on the variant to decide whether a failure is worth retrying
+ * and, for the API, which status code to respond with.
+ */
+#[derive(Debug)]
+pub enum AssetWatcherError {
+    Rpc(String),
+    Db(String),
+    PayloadParse(String),
+    MissingMarket(String),
+    MissingConditionalVault(String),
+    MissingProposal(String),
+    MissingTokenRecord(String),
+    MissingTransaction(String),
+    InvalidPubkey(String),
+}
+
+impl AssetWatcherError {
+    /// Transient failures (network, db, rows that have not been indexed yet) may succeed on a later attempt.
+    /// A missing transaction is not one of them: its event is written in the same db transaction as the row.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AssetWatcherError::Rpc(_)
+            | AssetWatcherError::Db(_)
+            | AssetWatcherError::MissingMarket(_)
+            | AssetWatcherError::MissingConditionalVault(_)
+            | AssetWatcherError::MissingProposal(_)
+            | AssetWatcherError::MissingTokenRecord(_) => true,
+            AssetWatcherError::PayloadParse(_)
+            | AssetWatcherError::MissingTransaction(_)
+            | AssetWatcherError::InvalidPubkey(_) => false,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AssetWatcherError::Rpc(_) => "rpc",
+            AssetWatcherError::Db(_) => "db",
+            AssetWatcherError::PayloadParse(_) => "payload_parse",
+            AssetWatcherError::MissingMarket(_) => "missing_market",
+            AssetWatcherError::MissingConditionalVault(_) => "missing_conditional_vault",
+            AssetWatcherError::MissingProposal(_) => "missing_proposal",
+            AssetWatcherError::MissingTokenRecord(_) => "missing_token_record",
+            AssetWatcherError::MissingTransaction(_) => "missing_transaction",
+            AssetWatcherError::InvalidPubkey(_) => "invalid_pubkey",
+        }
+    }
+}
+
+impl fmt::Display for AssetWatcherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetWatcherError::Rpc(msg) => write!(f, "rpc error: {}", msg),
+            AssetWatcherError::Db(msg) => write!(f, "db error: {}", msg),
+            AssetWatcherError::PayloadParse(msg) => write!(f, "payload parse error: {}", msg),
+            AssetWatcherError::MissingMarket(acct) => write!(f, "market not found: {}", acct),
+            AssetWatcherError::MissingConditionalVault(acct) => {
+                write!(f, "conditional vault not found: {}", acct)
+            }
+            AssetWatcherError::MissingProposal(acct) => write!(f, "proposal not found: {}", acct),
+            AssetWatcherError::MissingTokenRecord(acct) => {
+                write!(f, "token record not found: {}", acct)
+            }
+            AssetWatcherError::MissingTransaction(sig) => {
+                write!(f, "transaction not found: {}", sig)
+            }
+            AssetWatcherError::InvalidPubkey(msg) => write!(f, "invalid pubkey: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AssetWatcherError {}
+
+impl From<diesel::result::Error> for AssetWatcherError {
+    fn from(e: diesel::result::Error) -> Self {
+        AssetWatcherError::Db(e.to_string())
+    }
+}
+
+impl From<deadpool_diesel::PoolError> for AssetWatcherError {
+    fn from(e: deadpool_diesel::PoolError) -> Self {
+        AssetWatcherError::Db(e.to_string())
+    }
+}
+
+impl From<deadpool_diesel::InteractError> for AssetWatcherError {
+    fn from(e: deadpool_diesel::InteractError) -> Self {
+        AssetWatcherError::Db(e.to_string())
+    }
+}
+
+impl From<tokio_postgres::Error> for AssetWatcherError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        AssetWatcherError::Db(e.to_string())
+    }
+}
+
+impl From<ClientError> for AssetWatcherError {
+    fn from(e: ClientError) -> Self {
+        AssetWatcherError::Rpc(e.to_string())
+    }
+}
+
+impl From<PubsubClientError> for AssetWatcherError {
+    fn from(e: PubsubClientError) -> Self {
+        AssetWatcherError::Rpc(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AssetWatcherError {
+    fn from(e: serde_json::Error) -> Self {
+        AssetWatcherError::PayloadParse(e.to_string())
+    }
+}
+
+impl From<std::num::ParseIntError> for AssetWatcherError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        AssetWatcherError::PayloadParse(e.to_string())
+    }
+}
+
+impl From<ProgramError> for AssetWatcherError {
+    fn from(e: ProgramError) -> Self {
+        AssetWatcherError::PayloadParse(e.to_string())
+    }
+}
+
+impl From<ParsePubkeyError> for AssetWatcherError {
+    fn from(e: ParsePubkeyError) -> Self {
+        AssetWatcherError::InvalidPubkey(e.to_string())
+    }
+}
@@ -2,58 +2,199 @@
 extern crate diesel;
 extern crate dotenv;
 
-use diesel::prelude::*;
-use std::{env, sync::Arc};
+use std::env;
+use std::sync::Arc;
 use tokio::signal;
 mod adapters;
+mod config;
 mod entities;
 mod entrypoints;
+mod errors;
 mod services;
-use deadpool::managed::Object;
-use deadpool_diesel::postgres::{Pool, Runtime};
-use deadpool_diesel::Manager;
-use tokio::task::{self};
-
-async fn get_database_pool(
-    db_url: &str,
-) -> Result<Arc<Object<Manager<PgConnection>>>, Box<dyn std::error::Error>> {
-    let manager = Manager::new(db_url, Runtime::Tokio1);
-    let pool = Pool::builder(manager).max_size(8).build()?;
-    let conn_manager = pool.get().await?;
-    Ok(Arc::new(conn_manager))
+mod shutdown;
+use config::Config;
+use deadpool_diesel::postgres::{Manager, Pool, Runtime};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use shutdown::Shutdown;
+
+// the schema the entities are written against, including the triggers that feed the event outbox
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+fn get_database_pool(config: &Config) -> Result<Pool, Box<dyn std::error::Error>> {
+    let manager = Manager::new(&config.database_url, Runtime::Tokio1);
+    let pool = Pool::builder(manager)
+        .max_size(config.pool.max_size)
+        .wait_timeout(Some(config.pool.wait_timeout))
+        .create_timeout(Some(config.pool.create_timeout))
+        .recycle_timeout(Some(config.pool.recycle_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()?;
+    Ok(pool)
+}
+
+async fn run_migrations(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let applied = pool
+        .get()
+        .await?
+        .interact(|conn| {
+            conn.run_pending_migrations(MIGRATIONS)
+                .map(|versions| {
+                    versions
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<String>>()
+                })
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    if applied.is_empty() {
+        println!("database schema is up to date");
+    } else {
+        println!("applied migrations: {}", applied.join(", "));
+    }
+    Ok(())
 }
 
 async fn run_jobs(
-    pg_connection: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
+    config: Arc<Config>,
+    shutdown: Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    entrypoints::jobs::transaction_indexing::run_job(pg_connection).await?;
+    entrypoints::jobs::transaction_indexing::run_job(pool, config, shutdown).await?;
+    Ok(())
+}
+
+async fn wait_for_shutdown_signal() -> Result<(), Box<dyn std::error::Error>> {
+    let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        res = signal::ctrl_c() => {
+            res?;
+            println!("Received CTRL+C, shutting down.");
+        }
+        _ = sigterm.recv() => println!("Received SIGTERM, shutting down."),
+    }
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let pool = get_database_pool(&config)?;
+    // fail fast if the database is unreachable instead of on the first event
+    drop(pool.get().await?);
 
-    let pub_sub_client = adapters::rpc::get_pubsub_client().await?;
-    let db = get_database_pool(&database_url).await?;
+    // `asset-watcher migrate` only brings the schema up to date, e.g. as a deploy step
+    let migrate_only = env::args().nth(1).as_deref() == Some("migrate");
+    if migrate_only || config.run_migrations {
+        run_migrations(&pool).await?;
+    }
+    if migrate_only {
+        return Ok(());
+    }
 
-    let db_clone = Arc::clone(&db);
-    let db_clone_2 = Arc::clone(&db);
+    let pub_sub_client = adapters::rpc::get_pubsub_client(&config.rpc_endpoint_wss).await?;
 
-    let database_url_copy = database_url.clone();
-    task::spawn(async move {
-        entrypoints::events::setup::setup_event_listeners(&database_url_copy, db, pub_sub_client)
-            .await
+    let shutdown = Shutdown::new();
+    let workers = entrypoints::events::worker_pool::EventWorkers::new(&config, &shutdown);
+    let subscriptions = entrypoints::events::subscriptions::SubscriptionManager::new(
+        Arc::clone(&config),
+        pool.clone(),
+        pub_sub_client,
+        workers.account_updates.clone(),
+        shutdown.clone(),
+    );
+
+    let pool_for_events = pool.clone();
+    let pool_for_api = pool.clone();
+
+    let config_for_events = Arc::clone(&config);
+    let shutdown_for_events = shutdown.clone();
+    let subscriptions_for_events = subscriptions.clone();
+    let workers_for_events = workers.clone();
+    shutdown.spawn(async move {
+        entrypoints::events::setup::setup_event_listeners(
+            config_for_events,
+            pool_for_events,
+            subscriptions_for_events,
+            workers_for_events,
+            shutdown_for_events,
+        )
+        .await
     });
 
     // run the API
-    task::spawn(async move { entrypoints::http::routes::listen_and_serve(db_clone).await });
+    let config_for_api = Arc::clone(&config);
+    let shutdown_for_api = shutdown.clone();
+    shutdown.spawn(async move {
+        entrypoints::http::routes::listen_and_serve(
+            pool_for_api,
+            config_for_api,
+            subscriptions,
+            workers,
+            shutdown_for_api,
+        )
+        .await
+    });
+
+    // drifted balances are corrected periodically
+    let shutdown_for_reconciliation = shutdown.clone();
+    let config_for_reconciliation = Arc::clone(&config);
+    let pool_for_reconciliation = pool.clone();
+    shutdown.spawn(async move {
+        entrypoints::jobs::balance_reconciliation::run_job(
+            pool_for_reconciliation,
+            config_for_reconciliation,
+            shutdown_for_reconciliation,
+        )
+        .await
+    });
+
+    // failed indexing is retried from the dead letters
+    let shutdown_for_dead_letters = shutdown.clone();
+    let config_for_dead_letters = Arc::clone(&config);
+    let pool_for_dead_letters = pool.clone();
+    shutdown.spawn(async move {
+        entrypoints::jobs::dead_letter_retry::run_job(
+            pool_for_dead_letters,
+            config_for_dead_letters,
+            shutdown_for_dead_letters,
+        )
+        .await
+    });
+
+    // backfill runs alongside the listeners and API so a shutdown signal is never blocked on it
+    let shutdown_for_jobs = shutdown.clone();
+    let config_for_jobs = Arc::clone(&config);
+    shutdown.spawn(async move {
+        if let Err(e) = run_jobs(pool, config_for_jobs, shutdown_for_jobs).await {
+            eprintln!("error running jobs: {}", e);
+        }
+    });
 
-    // TODO setup API and watchers before running backfill...
-    run_jobs(db_clone_2).await?;
+    wait_for_shutdown_signal().await?;
+    shutdown.trigger();
 
-    signal::ctrl_c().await?;
-    println!("Received CTRL+C, shutting down.");
+    println!(
+        "waiting up to {:?} for {} in-flight tasks",
+        config.shutdown_timeout,
+        shutdown.in_flight()
+    );
+    if shutdown.drain(config.shutdown_timeout).await {
+        println!("all in-flight work finished, exiting.");
+    } else {
+        eprintln!(
+            "shutdown timeout hit with {} tasks still running, exiting anyway.",
+            shutdown.in_flight()
+        );
+    }
     Ok(())
 }
@@ -6,11 +6,13 @@ use crate::entities::auth::{
     AuthSessionResponse,
 };
 
+#[allow(dead_code)]
 pub struct AuthClient {
     client: Client,
     base_url: String,
 }
 
+#[allow(dead_code)]
 impl AuthClient {
     pub fn new(base_url: &str) -> Self {
         Self {
@@ -31,10 +33,7 @@ impl AuthClient {
             Ok(session_response)
         } else {
             let error_response = resp.json::<AuthErrorResponse>().await?;
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                error_response.error,
-            )))
+            Err(Box::new(std::io::Error::other(error_response.error)))
         }
     }
 
@@ -56,10 +55,7 @@ impl AuthClient {
             Ok(message_response)
         } else {
             let error_response = resp.json::<AuthErrorResponse>().await?;
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                error_response.error,
-            )))
+            Err(Box::new(std::io::Error::other(error_response.error)))
         }
     }
 
@@ -74,10 +70,7 @@ impl AuthClient {
             Ok(message_response)
         } else {
             let error_response = resp.json::<AuthErrorResponse>().await?;
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                error_response.error,
-            )))
+            Err(Box::new(std::io::Error::other(error_response.error)))
         }
     }
 }
@@ -0,0 +1,235 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiMessage,
+    UiTransactionEncoding, UiTransactionTokenBalance,
+};
+
+use crate::entities::token_acct_balances::token_acct_balances;
+use crate::entities::token_accts::TokenAcct;
+use crate::entities::transactions::transactions;
+use crate::errors::AssetWatcherError;
+
+use super::transactions::handle_token_acct_balance_tx;
+
+// the most signatures getSignaturesForAddress returns per page
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
+/**
+ * Fills in balance changes we missed for a token acct, e.g. while its subscription was down.
+ * Walks its signatures from newest back to the last slot in token_acct_balances, then replays
+ * them oldest first so each change gets its own row with the right tx_sig, slot and delta.
+ * Accts with no recorded balance yet have nothing to anchor to and are left alone.
+ * Returns how many balance changes were written.
+ */
+pub async fn backfill_token_acct_gap(
+    pool: Pool,
+    rpc_client: &RpcClient,
+    token_acct_record: &TokenAcct,
+    max_signatures: usize,
+) -> Result<usize, AssetWatcherError> {
+    let token_acct = token_acct_record.token_acct.clone();
+    let token_acct_pubkey = Pubkey::from_str(&token_acct)?;
+    let token_acct_for_query = token_acct.clone();
+    let last_recorded_slot: Option<BigDecimal> = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            token_acct_balances::table
+                .filter(token_acct_balances::token_acct.eq(token_acct_for_query))
+                .order_by(token_acct_balances::slot.desc())
+                .select(token_acct_balances::slot)
+                .first::<BigDecimal>(conn)
+                .optional()
+        })
+        .await??;
+    let Some(last_recorded_slot) = last_recorded_slot else {
+        return Ok(0);
+    };
+
+    let missed_signatures = get_signatures_after_slot(
+        rpc_client,
+        &token_acct_pubkey,
+        &last_recorded_slot,
+        max_signatures,
+    )
+    .await?;
+    if missed_signatures.is_empty() {
+        return Ok(0);
+    }
+    println!(
+        "backfilling {} missed signatures for token acct {} after slot {}",
+        missed_signatures.len(),
+        token_acct,
+        last_recorded_slot
+    );
+
+    let mut written = 0;
+    for signature_info in missed_signatures.into_iter().rev() {
+        let signature = Signature::from_str(&signature_info.signature).map_err(|e| {
+            AssetWatcherError::PayloadParse(format!(
+                "invalid signature {}: {}",
+                signature_info.signature, e
+            ))
+        })?;
+        let transaction = rpc_client
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(history_commitment(rpc_client)),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await?;
+        let Some(amount) = find_token_balance(&transaction, &token_acct) else {
+            continue;
+        };
+
+        handle_token_acct_balance_tx(
+            pool.clone(),
+            token_acct.clone(),
+            amount,
+            indexed_tx_sig(pool.clone(), signature_info.signature).await?,
+            BigDecimal::from(transaction.slot),
+            token_acct_record.mint_acct.clone(),
+            token_acct_record.owner_acct.clone(),
+        )
+        .await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Successful signatures for the acct newer than the slot, newest first.
+async fn get_signatures_after_slot(
+    rpc_client: &RpcClient,
+    token_acct_pubkey: &Pubkey,
+    last_recorded_slot: &BigDecimal,
+    max_signatures: usize,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AssetWatcherError> {
+    let mut signatures = vec![];
+    let mut before = None;
+    loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                token_acct_pubkey,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_SIZE),
+                    commitment: Some(history_commitment(rpc_client)),
+                },
+            )
+            .await?;
+        let page_len = page.len();
+        let Some(oldest) = page.last() else {
+            return Ok(signatures);
+        };
+        before = Some(Signature::from_str(&oldest.signature).map_err(|e| {
+            AssetWatcherError::PayloadParse(format!(
+                "invalid signature {}: {}",
+                oldest.signature, e
+            ))
+        })?);
+
+        for signature_info in page {
+            if BigDecimal::from(signature_info.slot) <= *last_recorded_slot {
+                return Ok(signatures);
+            }
+            if signature_info.err.is_some() {
+                continue;
+            }
+            if signatures.len() >= max_signatures {
+                eprintln!(
+                    "gap for token acct {} is over {} signatures, backfilling only the newest",
+                    token_acct_pubkey, max_signatures
+                );
+                return Ok(signatures);
+            }
+            signatures.push(signature_info);
+        }
+        if page_len < SIGNATURE_PAGE_SIZE {
+            return Ok(signatures);
+        }
+    }
+}
+
+/**
+ * The acct's balance after the transaction, from its post token balances.
+ * An acct that only shows up in the pre token balances was closed, so its balance is zero.
+ */
+fn find_token_balance(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    token_acct: &str,
+) -> Option<BigDecimal> {
+    let meta = transaction.transaction.meta.as_ref()?;
+    let EncodedTransaction::Json(ui_transaction) = &transaction.transaction.transaction else {
+        return None;
+    };
+    let UiMessage::Raw(message) = &ui_transaction.message else {
+        return None;
+    };
+
+    // account indexes cover the static keys followed by any loaded from lookup tables
+    let mut account_keys = message.account_keys.clone();
+    if let OptionSerializer::Some(loaded_addresses) = &meta.loaded_addresses {
+        account_keys.extend(loaded_addresses.writable.iter().cloned());
+        account_keys.extend(loaded_addresses.readonly.iter().cloned());
+    }
+    let account_index = account_keys.iter().position(|key| key == token_acct)?;
+    let has_acct =
+        |balance: &&UiTransactionTokenBalance| balance.account_index as usize == account_index;
+
+    if let OptionSerializer::Some(post_balances) = &meta.post_token_balances {
+        if let Some(post_balance) = post_balances.iter().find(has_acct) {
+            return BigDecimal::from_str(&post_balance.ui_token_amount.amount).ok();
+        }
+    }
+    match &meta.pre_token_balances {
+        OptionSerializer::Some(pre_balances) if pre_balances.iter().any(|b| has_acct(&b)) => {
+            Some(BigDecimal::from(0))
+        }
+        _ => None,
+    }
+}
+
+/// getSignaturesForAddress and getTransaction do not serve processed commitment.
+fn history_commitment(rpc_client: &RpcClient) -> CommitmentConfig {
+    if rpc_client.commitment().is_at_least_confirmed() {
+        rpc_client.commitment()
+    } else {
+        CommitmentConfig::confirmed()
+    }
+}
+
+/// token_acct_balances.tx_sig references transactions, so only link sigs we have indexed.
+async fn indexed_tx_sig(
+    pool: Pool,
+    signature: String,
+) -> Result<Option<String>, AssetWatcherError> {
+    let signature_for_query = signature.clone();
+    let exists = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            transactions::table
+                .filter(transactions::tx_sig.eq(signature_for_query))
+                .count()
+                .get_result::<i64>(conn)
+        })
+        .await??
+        > 0;
+    Ok(exists.then_some(signature))
+}
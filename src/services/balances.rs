@@ -1,32 +1,26 @@
-use crate::entities::token_acct_balances::token_acct_balances;
-use crate::entities::token_acct_balances::TokenAcctBalances;
 use crate::entities::token_accts::token_accts;
 
 use crate::entities::token_accts::TokenAcct;
 use crate::entities::token_accts::TokenAcctStatus;
 use crate::entities::tokens;
 use crate::entities::transactions::Payload;
+use crate::errors::AssetWatcherError;
 use bigdecimal::BigDecimal;
 use chrono::Utc;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
-use diesel::PgConnection;
 use serde_json::Value;
 use solana_account_decoder::parse_account_data::ParsedAccount;
 use solana_client::rpc_response::RpcResponseContext;
-use std::io;
-use std::io::ErrorKind;
-use std::sync::Arc;
 
 use super::transactions;
 
 pub async fn handle_token_acct_change(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     record: TokenAcct,
     updated_token_account: ParsedAccount,
     ctx: RpcResponseContext,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), AssetWatcherError> {
     // Parse the object
     let parsed_object = updated_token_account.parsed.as_object();
 
@@ -49,93 +43,37 @@ pub async fn handle_token_acct_change(
         .get("amount")
         .and_then(Value::as_str)
         .ok_or_else(|| {
-            println!("amount not found or invalid");
-            Box::new(io::Error::new(
-                ErrorKind::InvalidData,
-                "amount not found or invalid",
-            ))
+            AssetWatcherError::PayloadParse("amount not found or invalid".to_string())
         })?;
 
     let new_amount: i64 = new_amount_str.parse().map_err(|_| {
-        println!("Failed to parse amount as i64");
-        Box::new(io::Error::new(
-            ErrorKind::InvalidData,
-            "Failed to parse amount as i64",
+        AssetWatcherError::PayloadParse(format!(
+            "failed to parse amount as i64: {}",
+            new_amount_str
         ))
     })?;
 
-    // Query the most recent value for the given token_acct to calculate the delta
-    let record_clone = record.clone();
-    let previous_balance = conn_manager
-        .interact(move |conn| {
-            token_acct_balances::table
-                .filter(token_acct_balances::dsl::token_acct.eq(record_clone.token_acct.clone()))
-                .order_by(token_acct_balances::dsl::slot.desc())
-                .select(token_acct_balances::dsl::amount)
-                .first::<BigDecimal>(conn)
-                .optional()
-        })
-        .await??;
-
-    let previous_balance = match previous_balance {
-        Some(prev) => prev,
-        None => BigDecimal::from(0),
-    };
-
-    let new_amnt_decimal = BigDecimal::from(new_amount);
-    let new_delta = BigDecimal::from(new_amount) - previous_balance;
-
-    let new_balance = TokenAcctBalances {
-        token_acct: record.token_acct.clone(),
-        mint_acct: record.mint_acct.clone(),
-        owner_acct: record.owner_acct.clone(),
-        amount: new_amnt_decimal,
-        delta: new_delta,
-        slot: BigDecimal::from(ctx.slot),
-        created_at: Utc::now(),
-        tx_sig: None,
-    };
-
-    let conn_manager_clone = conn_manager.clone();
-    conn_manager_clone
-        .interact(move |conn| {
-            diesel::insert_into(token_acct_balances::table)
-                .values(new_balance)
-                .execute(conn)
-        })
-        .await??;
-
-    let now = Utc::now();
-    let mut token_balance: TokenAcct = record;
-    token_balance.amount = BigDecimal::from(new_amount);
-    token_balance.updated_at = Some(now);
-
-    conn_manager
-        .interact(move |conn| {
-            diesel::update(
-                token_accts::table
-                    .filter(token_accts::token_acct.eq(token_balance.token_acct.clone())),
-            )
-            .set((
-                token_accts::amount.eq(BigDecimal::from(new_amount)),
-                token_accts::dsl::updated_at.eq(Utc::now()),
-            ))
-            .execute(conn)
-        })
-        .await??;
-
-    Ok(())
+    transactions::handle_token_acct_balance_tx(
+        pool,
+        record.token_acct,
+        BigDecimal::from(new_amount),
+        None,
+        BigDecimal::from(ctx.slot),
+        record.mint_acct,
+        record.owner_acct,
+    )
+    .await
 }
 
 // TODO make this be able to run without updating token_acct to watching
 pub async fn handle_token_acct_in_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     transaction_payload: &Payload,
     transaction_sig: String,
     mint_acct_value: &str,
     token_account: &str,
     authority_account: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), AssetWatcherError> {
     let mint_acct_value_str = mint_acct_value.to_string();
     let token_account_str = token_account.to_string();
     let authority_account_str = authority_account.to_string();
@@ -143,7 +81,9 @@ pub async fn handle_token_acct_in_tx(
 
     // Check if the token record exists
     let mint_acct_value_clone = mint_acct_value_str.clone();
-    let token_record_exists = conn_manager
+    let token_record_exists = pool
+        .get()
+        .await?
         .interact(move |db| {
             tokens::tokens::table
                 .filter(tokens::tokens::dsl::mint_acct.eq(mint_acct_value_clone))
@@ -166,21 +106,33 @@ pub async fn handle_token_acct_in_tx(
         .accounts
         .iter()
         .find(|acc| acc.pubkey == token_account_str)
-        .ok_or("Matching account not found in transaction payload")?;
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(format!(
+                "matching account not found in transaction payload: {}",
+                token_account_str
+            ))
+        })?;
 
     let account_balance = match &account_with_balance.post_token_balance {
         Some(token_balance) => token_balance
             .amount
             .split(':')
             .nth(1)
-            .ok_or("Invalid postBalance format")?
+            .ok_or_else(|| {
+                AssetWatcherError::PayloadParse(format!(
+                    "invalid postBalance format: {}",
+                    token_balance.amount
+                ))
+            })?
             .parse::<i64>()?,
         None => 0,
     };
 
     // Check if the token account already exists
     let token_account_clone = token_account_str.clone();
-    let token_acct_record: Vec<TokenAcct> = conn_manager
+    let token_acct_record: Vec<TokenAcct> = pool
+        .get()
+        .await?
         .interact(move |db| {
             token_accts::table
                 .filter(token_accts::dsl::token_acct.eq(token_account_clone))
@@ -199,16 +151,20 @@ pub async fn handle_token_acct_in_tx(
         };
 
         let new_token_acct_clone = new_token_acct.clone();
-        conn_manager
+        pool.get()
+            .await?
             .interact(move |db| {
                 diesel::insert_into(token_accts::table)
                     .values(&new_token_acct_clone)
+                    // another indexer for the same tx may have just inserted it
+                    .on_conflict_do_nothing()
                     .execute(db)
             })
             .await??;
     } else {
         let token_account_update = token_account_str.clone();
-        conn_manager
+        pool.get()
+            .await?
             .interact(move |db| {
                 diesel::update(
                     token_accts::table.filter(token_accts::token_acct.eq(token_account_update)),
@@ -223,7 +179,7 @@ pub async fn handle_token_acct_in_tx(
     }
 
     transactions::handle_token_acct_balance_tx(
-        conn_manager.clone(),
+        pool.clone(),
         token_account_str.clone(),
         BigDecimal::from(account_balance),
         Some(transaction_sig_str),
@@ -236,3 +192,66 @@ pub async fn handle_token_acct_in_tx(
     Ok(())
 }
 
+/**
+ * Handles a token acct seen through a program subscription (owner or mint watch).
+ * New token accts are inserted as Enabled, since the program subscription already covers them
+ * and an account subscription on top would write every balance change twice.
+ */
+pub async fn handle_program_token_acct(
+    pool: Pool,
+    token_account_str: String,
+    token_account: spl_token::state::Account,
+    slot: u64,
+) -> Result<(), AssetWatcherError> {
+    let new_balance = BigDecimal::from(token_account.amount);
+    let mint_acct_value_str = token_account.mint.to_string();
+    let owner_acct_str = token_account.owner.to_string();
+
+    let token_account_clone = token_account_str.clone();
+    let token_acct_record: Option<TokenAcct> = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            token_accts::table
+                .filter(token_accts::dsl::token_acct.eq(token_account_clone))
+                .first::<TokenAcct>(db)
+                .optional()
+        })
+        .await??;
+
+    match token_acct_record {
+        // only the delegate, close authority or similar changed
+        Some(record) if record.amount == new_balance => return Ok(()),
+        Some(_) => (),
+        None => {
+            let new_token_acct = TokenAcct {
+                token_acct: token_account_str.clone(),
+                owner_acct: owner_acct_str.clone(),
+                amount: new_balance.clone(),
+                status: TokenAcctStatus::Enabled,
+                mint_acct: mint_acct_value_str.clone(),
+                updated_at: Some(Utc::now()),
+            };
+            pool.get()
+                .await?
+                .interact(move |db| {
+                    diesel::insert_into(token_accts::table)
+                        .values(&new_token_acct)
+                        .on_conflict_do_nothing()
+                        .execute(db)
+                })
+                .await??;
+        }
+    }
+
+    transactions::handle_token_acct_balance_tx(
+        pool,
+        token_account_str,
+        new_balance,
+        None,
+        BigDecimal::from(slot),
+        mint_acct_value_str,
+        owner_acct_str,
+    )
+    .await
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Duration, Utc};
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+use crate::entities::indexing_dead_letters::{indexing_dead_letters, IndexingDeadLetter};
+use crate::entities::transactions::{transactions, Transaction};
+use crate::errors::AssetWatcherError;
+
+// a dead lettered tx is given up on after this many failed attempts
+const MAX_ATTEMPTS: i32 = 10;
+const BACKOFF_INITIAL_SECS: i64 = 60;
+const BACKOFF_MAX_SECS: i64 = 6 * 60 * 60;
+
+/**
+ * Records a failed indexing attempt for the tx, scheduling the next retry with exponential backoff.
+ * The tx is left without a next retry once it ran out of attempts or the error cannot go away.
+ */
+pub async fn record_failure(
+    pool: Pool,
+    transaction_sig: String,
+    error_kind: &'static str,
+    error: String,
+    retryable: bool,
+) -> Result<IndexingDeadLetter, AssetWatcherError> {
+    let entry = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            conn.transaction(|db| {
+                let existing = indexing_dead_letters::table
+                    .find(&transaction_sig)
+                    .for_update()
+                    .first::<IndexingDeadLetter>(db)
+                    .optional()?;
+
+                let entry = next_entry(
+                    existing,
+                    transaction_sig,
+                    error_kind,
+                    error,
+                    retryable,
+                    Utc::now(),
+                );
+                diesel::insert_into(indexing_dead_letters::table)
+                    .values(&entry)
+                    .on_conflict(indexing_dead_letters::tx_sig)
+                    .do_update()
+                    .set(&entry)
+                    .execute(db)?;
+                Ok::<_, diesel::result::Error>(entry)
+            })
+        })
+        .await??;
+    Ok(entry)
+}
+
+/// The entry after one more failure, carried over from the existing one if the tx failed before.
+fn next_entry(
+    existing: Option<IndexingDeadLetter>,
+    transaction_sig: String,
+    error_kind: &'static str,
+    error: String,
+    retryable: bool,
+    now: DateTime<Utc>,
+) -> IndexingDeadLetter {
+    let attempts = existing.as_ref().map_or(1, |entry| entry.attempts + 1);
+    IndexingDeadLetter {
+        tx_sig: transaction_sig,
+        error_kind: error_kind.to_string(),
+        error,
+        attempts,
+        first_failed_at: existing.map_or(now, |entry| entry.first_failed_at),
+        last_failed_at: now,
+        next_retry_at: (retryable && attempts < MAX_ATTEMPTS).then(|| now + backoff(attempts)),
+    }
+}
+
+fn backoff(attempts: i32) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    Duration::seconds((BACKOFF_INITIAL_SECS << exponent).min(BACKOFF_MAX_SECS))
+}
+
+/// Removes the tx from the dead letters, returning whether it was there.
+pub async fn clear(pool: Pool, transaction_sig: String) -> Result<bool, AssetWatcherError> {
+    let deleted = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            diesel::delete(indexing_dead_letters::table.find(transaction_sig)).execute(conn)
+        })
+        .await??;
+    Ok(deleted > 0)
+}
+
+/// Transactions whose next retry is due, longest waiting first.
+pub async fn load_due(pool: Pool, limit: i64) -> Result<Vec<Transaction>, AssetWatcherError> {
+    let txs = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            let due_sigs: Vec<String> = indexing_dead_letters::table
+                .select(indexing_dead_letters::tx_sig)
+                .filter(indexing_dead_letters::next_retry_at.le(Utc::now()))
+                .order(indexing_dead_letters::next_retry_at.asc())
+                .limit(limit)
+                .load(conn)?;
+            transactions::table
+                .filter(transactions::tx_sig.eq_any(due_sigs))
+                .load::<Transaction>(conn)
+        })
+        .await??;
+    Ok(txs)
+}
+
+/// Every dead lettered tx, or only those no longer retried when `stuck_only` is set, oldest first.
+pub async fn list(
+    pool: Pool,
+    stuck_only: bool,
+) -> Result<Vec<IndexingDeadLetter>, AssetWatcherError> {
+    let entries = pool
+        .get()
+        .await?
+        .interact(move |conn| {
+            let mut query = indexing_dead_letters::table.into_boxed();
+            if stuck_only {
+                query = query.filter(indexing_dead_letters::next_retry_at.is_null());
+            }
+            query
+                .order(indexing_dead_letters::first_failed_at.asc())
+                .load::<IndexingDeadLetter>(conn)
+        })
+        .await??;
+    Ok(entries)
+}
+
+#[cfg(test)]
+#[path = "dead_letters_test.rs"]
+mod tests;
@@ -0,0 +1,115 @@
+use super::*;
+use diesel::pg::Pg;
+
+fn failed_entry(attempts: i32, now: DateTime<Utc>) -> IndexingDeadLetter {
+    IndexingDeadLetter {
+        tx_sig: "sig".to_string(),
+        error_kind: "rpc".to_string(),
+        error: "rpc error".to_string(),
+        attempts,
+        first_failed_at: now - Duration::days(1),
+        last_failed_at: now - Duration::hours(1),
+        next_retry_at: Some(now),
+    }
+}
+
+#[test]
+fn test_backoff_doubles_up_to_the_max() {
+    assert_eq!(backoff(1), Duration::seconds(BACKOFF_INITIAL_SECS));
+    assert_eq!(backoff(2), Duration::seconds(BACKOFF_INITIAL_SECS * 2));
+    assert_eq!(backoff(3), Duration::seconds(BACKOFF_INITIAL_SECS * 4));
+    assert_eq!(backoff(MAX_ATTEMPTS), Duration::seconds(BACKOFF_MAX_SECS));
+    assert_eq!(backoff(i32::MAX), Duration::seconds(BACKOFF_MAX_SECS));
+    assert_eq!(backoff(0), Duration::seconds(BACKOFF_INITIAL_SECS));
+}
+
+#[test]
+fn test_first_failure_is_scheduled_for_retry() {
+    let now = Utc::now();
+    let entry = next_entry(
+        None,
+        "sig".to_string(),
+        "rpc",
+        "rpc error".to_string(),
+        true,
+        now,
+    );
+
+    assert_eq!(entry.attempts, 1);
+    assert_eq!(entry.first_failed_at, now);
+    assert_eq!(entry.next_retry_at, Some(now + backoff(1)));
+}
+
+#[test]
+fn test_repeated_failure_keeps_first_failed_at() {
+    let now = Utc::now();
+    let existing = failed_entry(2, now);
+    let first_failed_at = existing.first_failed_at;
+    let entry = next_entry(
+        Some(existing),
+        "sig".to_string(),
+        "rpc",
+        "rpc error".to_string(),
+        true,
+        now,
+    );
+
+    assert_eq!(entry.attempts, 3);
+    assert_eq!(entry.first_failed_at, first_failed_at);
+    assert_eq!(entry.last_failed_at, now);
+    assert_eq!(entry.next_retry_at, Some(now + backoff(3)));
+}
+
+#[test]
+fn test_exhausted_or_permanent_failure_is_not_retried() {
+    let now = Utc::now();
+    let exhausted = next_entry(
+        Some(failed_entry(MAX_ATTEMPTS - 1, now)),
+        "sig".to_string(),
+        "rpc",
+        "rpc error".to_string(),
+        true,
+        now,
+    );
+    assert_eq!(exhausted.attempts, MAX_ATTEMPTS);
+    assert!(exhausted.next_retry_at.is_none());
+
+    let permanent = next_entry(
+        None,
+        "sig".to_string(),
+        "payload_parse",
+        "payload parse error".to_string(),
+        false,
+        now,
+    );
+    assert!(permanent.next_retry_at.is_none());
+}
+
+#[test]
+fn test_upsert_of_exhausted_entry_clears_next_retry_at() {
+    let now = Utc::now();
+    let exhausted = next_entry(
+        Some(failed_entry(MAX_ATTEMPTS - 1, now)),
+        "sig".to_string(),
+        "rpc",
+        "rpc error".to_string(),
+        true,
+        now,
+    );
+    let upsert = diesel::insert_into(indexing_dead_letters::table)
+        .values(&exhausted)
+        .on_conflict(indexing_dead_letters::tx_sig)
+        .do_update()
+        .set(&exhausted);
+    let sql = diesel::debug_query::<Pg, _>(&upsert).to_string();
+
+    let update_clause = sql
+        .split("DO UPDATE SET")
+        .nth(1)
+        .expect("upsert should update");
+    assert!(
+        update_clause.contains("\"next_retry_at\" = $"),
+        "next_retry_at is not set on conflict: {}",
+        sql
+    );
+}
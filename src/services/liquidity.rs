@@ -1,51 +1,44 @@
-use std::io;
-use std::sync::Arc;
-
+use crate::entities::lp_positions::{
+    lp_position_events, lp_positions, LpEventKind, LpPosition, LpPositionEvent,
+};
 use crate::entities::transactions::Instruction;
 use crate::entities::transactions::Payload;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::PgConnection;
+use crate::errors::AssetWatcherError;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+
+use super::{balances, transactions};
 
-use super::{balances,transactions};
+// first key of the advisory lock taken per lp position, distinct from the token acct one
+const LP_POSITION_LOCK_NAMESPACE: i32 = 2;
 
 pub async fn handle_lp_deposit_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     transaction_payload: &Payload,
     transaction_sig: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let lp_deposit_instruction = find_lp_deposit_instruction(&transaction_payload)?;
+) -> Result<(), AssetWatcherError> {
+    let lp_deposit_instruction = find_lp_deposit_instruction(transaction_payload)?;
     let authority_account = transactions::find_authority_account(&lp_deposit_instruction)?;
     let (lp_ata, lp_mint) = find_lp_mint_and_ata_account(&lp_deposit_instruction)?;
-    let mut lp_account_vec = vec![(lp_ata.as_str(), lp_mint)];
-    let amm_acct = lp_deposit_instruction
-        .accounts_with_data
-        .iter()
-        .find(|account| account.name == "amm")
-        .map(|account| account.pubkey.clone());
-
-    let amm_acct_str = match amm_acct {
-        Some(acct) => acct,
-        None => String::default().to_string(),
-    };
-
-    if amm_acct_str == "" {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "no amm_acct_str",
-        )));
-    }
+    let mut lp_account_vec = vec![(lp_ata.as_str(), lp_mint.clone())];
+    let amm_acct_str = transactions::find_account(&lp_deposit_instruction, &["amm"])?;
 
     let (base_mint, quote_mint) =
-        transactions::find_base_and_quote_mint(amm_acct_str, Arc::clone(&conn_manager)).await?;
+        transactions::find_base_and_quote_mint(amm_acct_str.clone(), pool.clone()).await?;
 
-    let mut relevant_accounts =
-        transactions::get_relevant_accounts_from_ix_and_mints(&lp_deposit_instruction, base_mint, quote_mint);
+    let mut relevant_accounts = transactions::get_relevant_accounts_from_ix_and_mints(
+        &lp_deposit_instruction,
+        base_mint,
+        quote_mint,
+    );
     relevant_accounts.append(&mut lp_account_vec);
 
     for (token_account, mint_acct_value) in &relevant_accounts {
         balances::handle_token_acct_in_tx(
-            Arc::clone(&conn_manager),
+            pool.clone(),
             transaction_payload,
             transaction_sig.clone(),
             mint_acct_value,
@@ -55,47 +48,42 @@ pub async fn handle_lp_deposit_tx(
         .await?
     }
 
-    Ok(())
+    let event = lp_position_event(
+        transaction_payload,
+        &lp_deposit_instruction,
+        LpEventKind::Deposit,
+        transaction_sig,
+        authority_account,
+        amm_acct_str,
+        lp_mint,
+    )?;
+    record_lp_position_event(pool, event).await
 }
 
-
 pub async fn handle_lp_withdrawal_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     transaction_payload: &Payload,
     transaction_sig: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let lp_withdrawal_instruction = find_lp_withdrawal_instruction(&transaction_payload)?;
+) -> Result<(), AssetWatcherError> {
+    let lp_withdrawal_instruction = find_lp_withdrawal_instruction(transaction_payload)?;
     let authority_account = transactions::find_authority_account(&lp_withdrawal_instruction)?;
     let (lp_ata, lp_mint) = find_lp_mint_and_ata_account(&lp_withdrawal_instruction)?;
-    let mut lp_account_vec = vec![(lp_ata.as_str(), lp_mint)];
-    let amm_acct = lp_withdrawal_instruction
-        .accounts_with_data
-        .iter()
-        .find(|account| account.name == "amm")
-        .map(|account| account.pubkey.clone());
-
-    let amm_acct_str = match amm_acct {
-        Some(acct) => acct,
-        None => String::default().to_string(),
-    };
-
-    if amm_acct_str == "" {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "This is an error message",
-        )));
-    }
+    let mut lp_account_vec = vec![(lp_ata.as_str(), lp_mint.clone())];
+    let amm_acct_str = transactions::find_account(&lp_withdrawal_instruction, &["amm"])?;
 
     let (base_mint, quote_mint) =
-        transactions::find_base_and_quote_mint(amm_acct_str, Arc::clone(&conn_manager)).await?;
+        transactions::find_base_and_quote_mint(amm_acct_str.clone(), pool.clone()).await?;
 
-    let mut relevant_accounts =
-        transactions::get_relevant_accounts_from_ix_and_mints(&lp_withdrawal_instruction, base_mint, quote_mint);
+    let mut relevant_accounts = transactions::get_relevant_accounts_from_ix_and_mints(
+        &lp_withdrawal_instruction,
+        base_mint,
+        quote_mint,
+    );
     relevant_accounts.append(&mut lp_account_vec);
 
     for (token_account, mint_acct_value) in &relevant_accounts {
         balances::handle_token_acct_in_tx(
-            Arc::clone(&conn_manager),
+            pool.clone(),
             transaction_payload,
             transaction_sig.clone(),
             mint_acct_value,
@@ -105,52 +93,249 @@ pub async fn handle_lp_withdrawal_tx(
         .await?
     }
 
-    Ok(())
+    let event = lp_position_event(
+        transaction_payload,
+        &lp_withdrawal_instruction,
+        LpEventKind::Withdrawal,
+        transaction_sig,
+        authority_account,
+        amm_acct_str,
+        lp_mint,
+    )?;
+    record_lp_position_event(pool, event).await
 }
 
 fn find_lp_deposit_instruction(
     transaction_payload: &Payload,
-) -> Result<Instruction, Box<dyn std::error::Error>> {
+) -> Result<Instruction, AssetWatcherError> {
     transaction_payload
         .instructions
         .iter()
         .find(|instruction| instruction.name == "addLiquidity")
         .cloned()
-        .ok_or_else(|| "addLiquidity instruction not found".into())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse("addLiquidity instruction not found".to_string())
+        })
 }
 
 fn find_lp_mint_and_ata_account(
-    lp_deposit_instruction: &Instruction,
-) -> Result<(String, String), Box<dyn std::error::Error>> {
-    let mint_res: Result<String, &str> = lp_deposit_instruction
-        .accounts_with_data
-        .iter()
-        .find(|account| account.name == "lpMint")
-        .map(|account| account.pubkey.clone())
-        .ok_or_else(|| "lpMint account not found in addLiquidity instruction");
-    let ata_res: Result<String, &str> = lp_deposit_instruction
-        .accounts_with_data
-        .iter()
-        .find(|account| account.name == "userLpAccount")
-        .map(|account| account.pubkey.clone())
-        .ok_or_else(|| "lpMint account not found in addLiquidity instruction");
-
-    match (mint_res, ata_res) {
-        (Ok(mint), Ok(ata)) => Ok((ata, mint)),
-        _ => Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "could not find lp accounts",
-        ))),
-    }
+    lp_instruction: &Instruction,
+) -> Result<(String, String), AssetWatcherError> {
+    let mint = transactions::find_account(lp_instruction, &["lpMint"])?;
+    let ata = transactions::find_account(lp_instruction, &["userLpAccount"])?;
+    Ok((ata, mint))
 }
 
 fn find_lp_withdrawal_instruction(
     transaction_payload: &Payload,
-) -> Result<Instruction, Box<dyn std::error::Error>> {
+) -> Result<Instruction, AssetWatcherError> {
     transaction_payload
         .instructions
         .iter()
-        .find(|instruction| instruction.name == "addLiquidity")
+        .find(|instruction| instruction.name == "removeLiquidity")
         .cloned()
-        .ok_or_else(|| "addLiquidity instruction not found".into())
-}
\ No newline at end of file
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse("removeLiquidity instruction not found".to_string())
+        })
+}
+
+/**
+ * What the user put in or took out, from the pre and post token balances of their accts.
+ * Only quoteAmount on deposits and lpTokensToBurn on withdrawals are exact amounts, so only those
+ * accts fall back to the instruction arg when the payload has no token balances for them. The
+ * other args (maxBaseAmount, minLpTokens, minBaseAmount, minQuoteAmount) are slippage bounds, and
+ * without token balances the event is a payload parse error rather than a guess.
+ */
+fn lp_position_event(
+    transaction_payload: &Payload,
+    lp_instruction: &Instruction,
+    kind: LpEventKind,
+    transaction_sig: String,
+    user_acct: String,
+    amm_acct: String,
+    lp_mint_acct: String,
+) -> Result<LpPositionEvent, AssetWatcherError> {
+    let (base_amount, quote_amount, lp_amount) = match kind {
+        LpEventKind::Deposit => (
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userBaseAccount",
+                false,
+                None,
+            )?,
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userQuoteAccount",
+                false,
+                Some("quoteAmount"),
+            )?,
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userLpAccount",
+                true,
+                None,
+            )?,
+        ),
+        LpEventKind::Withdrawal => (
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userBaseAccount",
+                true,
+                None,
+            )?,
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userQuoteAccount",
+                true,
+                None,
+            )?,
+            amount_moved(
+                transaction_payload,
+                lp_instruction,
+                "userLpAccount",
+                false,
+                Some("lpTokensToBurn"),
+            )?,
+        ),
+    };
+
+    Ok(LpPositionEvent {
+        tx_sig: transaction_sig,
+        user_acct,
+        amm_acct,
+        lp_mint_acct,
+        kind,
+        base_amount,
+        quote_amount,
+        lp_amount,
+        slot: BigDecimal::from(transaction_payload.slot),
+        block_time: transactions::payload_block_time(transaction_payload)?,
+    })
+}
+
+fn amount_moved(
+    transaction_payload: &Payload,
+    lp_instruction: &Instruction,
+    account_name: &str,
+    into_acct: bool,
+    exact_arg: Option<&str>,
+) -> Result<BigDecimal, AssetWatcherError> {
+    let token_acct = transactions::find_account(lp_instruction, &[account_name])?;
+    if let Some(change) = transactions::token_balance_change(transaction_payload, &token_acct)? {
+        return Ok(if into_acct { change } else { -change });
+    }
+    let exact_amount = match exact_arg {
+        Some(exact_arg) => transactions::amount_arg(lp_instruction, exact_arg)?,
+        None => None,
+    };
+    exact_amount.ok_or_else(|| {
+        AssetWatcherError::PayloadParse(format!(
+            "no token balances for {} and no exact amount arg in {} instruction",
+            account_name, lp_instruction.name
+        ))
+    })
+}
+
+/**
+ * Stores the event and refolds the user's position on the AMM from all of its events, under an
+ * advisory lock on the position. Refolding keeps the position right when a tx is indexed again
+ * or an older one lands late.
+ */
+async fn record_lp_position_event(
+    pool: Pool,
+    event: LpPositionEvent,
+) -> Result<(), AssetWatcherError> {
+    pool.get()
+        .await?
+        .interact(move |db| {
+            db.transaction(|db| {
+                diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+                    .bind::<Integer, _>(LP_POSITION_LOCK_NAMESPACE)
+                    .bind::<Text, _>(format!("{}:{}", event.user_acct, event.amm_acct))
+                    .execute(db)?;
+
+                diesel::insert_into(lp_position_events::table)
+                    .values(&event)
+                    .on_conflict((
+                        lp_position_events::tx_sig,
+                        lp_position_events::user_acct,
+                        lp_position_events::amm_acct,
+                    ))
+                    .do_update()
+                    .set(&event)
+                    .execute(db)?;
+
+                let events = lp_position_events::table
+                    .filter(lp_position_events::user_acct.eq(&event.user_acct))
+                    .filter(lp_position_events::amm_acct.eq(&event.amm_acct))
+                    .order((
+                        lp_position_events::slot.asc(),
+                        lp_position_events::tx_sig.asc(),
+                    ))
+                    .load::<LpPositionEvent>(db)?;
+                let position = fold_position(&event, &events);
+
+                diesel::insert_into(lp_positions::table)
+                    .values(&position)
+                    .on_conflict((lp_positions::user_acct, lp_positions::amm_acct))
+                    .do_update()
+                    .set(&position)
+                    .execute(db)?;
+                Ok::<_, diesel::result::Error>(())
+            })
+        })
+        .await??;
+    Ok(())
+}
+
+fn fold_position(latest: &LpPositionEvent, events: &[LpPositionEvent]) -> LpPosition {
+    let mut position = LpPosition {
+        user_acct: latest.user_acct.clone(),
+        amm_acct: latest.amm_acct.clone(),
+        lp_mint_acct: latest.lp_mint_acct.clone(),
+        lp_tokens_minted: BigDecimal::zero(),
+        lp_tokens_burned: BigDecimal::zero(),
+        base_deposited: BigDecimal::zero(),
+        quote_deposited: BigDecimal::zero(),
+        base_withdrawn: BigDecimal::zero(),
+        quote_withdrawn: BigDecimal::zero(),
+        base_cost_basis: BigDecimal::zero(),
+        quote_cost_basis: BigDecimal::zero(),
+        updated_at: Utc::now(),
+    };
+    for event in events {
+        match event.kind {
+            LpEventKind::Deposit => {
+                position.lp_tokens_minted += &event.lp_amount;
+                position.base_deposited += &event.base_amount;
+                position.quote_deposited += &event.quote_amount;
+                position.base_cost_basis += &event.base_amount;
+                position.quote_cost_basis += &event.quote_amount;
+            }
+            LpEventKind::Withdrawal => {
+                let held = &position.lp_tokens_minted - &position.lp_tokens_burned;
+                // burning everything held (or more, if deposits predate indexing) clears the basis
+                let remaining = if held > event.lp_amount {
+                    (&held - &event.lp_amount) / &held
+                } else {
+                    BigDecimal::zero()
+                };
+                position.base_cost_basis = (&position.base_cost_basis * &remaining).round(0);
+                position.quote_cost_basis = (&position.quote_cost_basis * &remaining).round(0);
+                position.lp_tokens_burned += &event.lp_amount;
+                position.base_withdrawn += &event.base_amount;
+                position.quote_withdrawn += &event.quote_amount;
+            }
+        }
+    }
+    position
+}
+
+#[cfg(test)]
+#[path = "liquidity_test.rs"]
+mod tests;
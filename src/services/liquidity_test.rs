@@ -0,0 +1,71 @@
+use super::*;
+use chrono::TimeZone;
+
+fn event(kind: LpEventKind, base: i64, quote: i64, lp: i64) -> LpPositionEvent {
+    LpPositionEvent {
+        tx_sig: "sig".to_string(),
+        user_acct: "user".to_string(),
+        amm_acct: "amm".to_string(),
+        lp_mint_acct: "lp_mint".to_string(),
+        kind,
+        base_amount: BigDecimal::from(base),
+        quote_amount: BigDecimal::from(quote),
+        lp_amount: BigDecimal::from(lp),
+        slot: BigDecimal::from(1),
+        block_time: Utc.timestamp_opt(0, 0).unwrap(),
+    }
+}
+
+#[test]
+fn test_fold_position_deposits_add_to_cost_basis() {
+    let events = vec![
+        event(LpEventKind::Deposit, 100, 200, 10),
+        event(LpEventKind::Deposit, 50, 100, 5),
+    ];
+    let position = fold_position(&events[1], &events);
+
+    assert_eq!(position.lp_tokens_minted, BigDecimal::from(15));
+    assert_eq!(position.base_deposited, BigDecimal::from(150));
+    assert_eq!(position.quote_deposited, BigDecimal::from(300));
+    assert_eq!(position.base_cost_basis, BigDecimal::from(150));
+    assert_eq!(position.quote_cost_basis, BigDecimal::from(300));
+}
+
+#[test]
+fn test_fold_position_partial_burn_reduces_cost_basis_by_share_burned() {
+    let events = vec![
+        event(LpEventKind::Deposit, 100, 200, 10),
+        event(LpEventKind::Withdrawal, 30, 70, 4),
+    ];
+    let position = fold_position(&events[1], &events);
+
+    assert_eq!(position.lp_tokens_burned, BigDecimal::from(4));
+    assert_eq!(position.base_withdrawn, BigDecimal::from(30));
+    assert_eq!(position.quote_withdrawn, BigDecimal::from(70));
+    assert_eq!(position.base_cost_basis, BigDecimal::from(60));
+    assert_eq!(position.quote_cost_basis, BigDecimal::from(120));
+}
+
+#[test]
+fn test_fold_position_over_burn_clears_cost_basis() {
+    let events = vec![
+        event(LpEventKind::Deposit, 100, 200, 10),
+        event(LpEventKind::Withdrawal, 150, 300, 15),
+    ];
+    let position = fold_position(&events[1], &events);
+
+    assert_eq!(position.lp_tokens_minted, BigDecimal::from(10));
+    assert_eq!(position.lp_tokens_burned, BigDecimal::from(15));
+    assert_eq!(position.base_cost_basis, BigDecimal::zero());
+    assert_eq!(position.quote_cost_basis, BigDecimal::zero());
+}
+
+#[test]
+fn test_fold_position_withdrawal_without_deposits_keeps_zero_basis() {
+    let events = vec![event(LpEventKind::Withdrawal, 30, 70, 4)];
+    let position = fold_position(&events[0], &events);
+
+    assert_eq!(position.lp_tokens_burned, BigDecimal::from(4));
+    assert_eq!(position.base_cost_basis, BigDecimal::zero());
+    assert_eq!(position.quote_cost_basis, BigDecimal::zero());
+}
@@ -1,25 +1,22 @@
-use std::sync::Arc;
-
 use crate::entities::transactions::Payload;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::PgConnection;
+use crate::errors::AssetWatcherError;
+use deadpool_diesel::postgres::Pool;
 
 use super::{balances, transactions};
 
 pub async fn handle_merge_conditional_tokens_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     transaction_payload: &Payload,
     transaction_sig: String,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), AssetWatcherError> {
     let mint_instruction = transactions::find_instruction(
-        &transaction_payload,
+        transaction_payload,
         "mergeConditionalTokensForUnderlyingTokens",
     )?;
     let authority_account = transactions::find_authority_account(&mint_instruction)?;
     let vault_account = transactions::find_vault_account(&mint_instruction)?;
     let conditional_vault =
-        transactions::get_conditional_vault(Arc::clone(&conn_manager), &vault_account).await?;
+        transactions::get_conditional_vault(pool.clone(), &vault_account).await?;
 
     let relevant_accounts = transactions::get_relevant_accounts_from_mint_and_vault(
         &mint_instruction,
@@ -28,7 +25,7 @@ pub async fn handle_merge_conditional_tokens_tx(
 
     for (token_account, mint_acct_value) in &relevant_accounts {
         balances::handle_token_acct_in_tx(
-            Arc::clone(&conn_manager),
+            pool.clone(),
             transaction_payload,
             transaction_sig.clone(),
             mint_acct_value,
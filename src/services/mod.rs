@@ -1,9 +1,13 @@
 pub mod auth;
+pub mod balance_history;
 pub mod balances;
+pub mod dead_letters;
 pub mod deposits;
 pub mod liquidity;
 pub mod merge_conditionals_for_underlying;
 pub mod new_mint;
+pub mod orders;
+pub mod proposals;
 pub mod redeem_conditionals;
 pub mod swaps;
 pub mod transactions;
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+use crate::entities::orders::{orders, Order, OrderAction, OrderSide};
+use crate::entities::transactions::{Instruction, Payload};
+use crate::errors::AssetWatcherError;
+
+use super::{balances, transactions};
+
+/**
+ * Records an Openbook placeOrder and the balance of the token acct paying for it:
+ * a bid pays quote tokens into the market vault and an ask pays base tokens.
+ */
+pub async fn handle_place_order_tx(
+    pool: Pool,
+    transaction_payload: &Payload,
+    transaction_sig: String,
+) -> Result<(), AssetWatcherError> {
+    let place_instruction = transactions::find_instruction(transaction_payload, "placeOrder")?;
+    let actor_acct = transactions::find_account(&place_instruction, &["signer", "owner"])?;
+    let market_acct = transactions::find_account(&place_instruction, &["market"])?;
+    let user_token_acct = transactions::find_account(&place_instruction, &["userTokenAccount"])?;
+    let args = order_args(&place_instruction);
+    let side = args
+        .get("side")
+        .and_then(|side| parse_side(side))
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse("side not found in placeOrder instruction".to_string())
+        })?;
+
+    let (base_mint, quote_mint) =
+        transactions::find_base_and_quote_mint(market_acct.clone(), pool.clone()).await?;
+
+    let order = Order {
+        order_tx_sig: transaction_sig.clone(),
+        action: OrderAction::Place,
+        market_acct,
+        actor_acct: actor_acct.clone(),
+        open_orders_acct: transactions::find_account(&place_instruction, &["openOrdersAccount"])
+            .ok(),
+        side: Some(side),
+        price_lots: lots_arg(&args, "priceLots")?,
+        base_lots: lots_arg(&args, "maxBaseLots")?,
+        quote_lots: lots_arg(&args, "maxQuoteLotsIncludingFees")?,
+        client_order_id: args.get("clientOrderId").cloned(),
+        order_id: None,
+        order_type: args.get("orderType").cloned(),
+        slot: BigDecimal::from(transaction_payload.slot),
+        order_time: transactions::payload_block_time(transaction_payload)?,
+    };
+    upsert_order(pool.clone(), order).await?;
+
+    let paying_mint = match side {
+        OrderSide::Bid => quote_mint,
+        OrderSide::Ask => base_mint,
+    };
+    balances::handle_token_acct_in_tx(
+        pool,
+        transaction_payload,
+        transaction_sig,
+        &paying_mint,
+        &user_token_acct,
+        &actor_acct,
+    )
+    .await
+}
+
+/**
+ * Records an Openbook cancelOrder. Cancelled funds go back to the open orders account
+ * and only reach a token acct when settled, so no balances change here.
+ */
+pub async fn handle_cancel_order_tx(
+    pool: Pool,
+    transaction_payload: &Payload,
+    transaction_sig: String,
+) -> Result<(), AssetWatcherError> {
+    let cancel_instruction = transactions::find_instruction(transaction_payload, "cancelOrder")?;
+    let args = order_args(&cancel_instruction);
+
+    let order = Order {
+        order_tx_sig: transaction_sig,
+        action: OrderAction::Cancel,
+        market_acct: transactions::find_account(&cancel_instruction, &["market"])?,
+        actor_acct: transactions::find_account(&cancel_instruction, &["signer", "owner"])?,
+        open_orders_acct: transactions::find_account(&cancel_instruction, &["openOrdersAccount"])
+            .ok(),
+        side: None,
+        price_lots: None,
+        base_lots: None,
+        quote_lots: None,
+        client_order_id: args.get("clientOrderId").cloned(),
+        order_id: args.get("orderId").cloned(),
+        order_type: None,
+        slot: BigDecimal::from(transaction_payload.slot),
+        order_time: transactions::payload_block_time(transaction_payload)?,
+    };
+    upsert_order(pool, order).await
+}
+
+async fn upsert_order(pool: Pool, order: Order) -> Result<(), AssetWatcherError> {
+    pool.get()
+        .await?
+        .interact(move |db| {
+            diesel::insert_into(orders::table)
+                .values(&order)
+                .on_conflict(orders::order_tx_sig)
+                .do_update()
+                .set(&order)
+                .execute(db)
+        })
+        .await??;
+    Ok(())
+}
+
+/// Instruction args by name; the fields of a struct arg (e.g. placeOrder's `args`) are flattened in.
+fn order_args(instruction: &Instruction) -> HashMap<String, String> {
+    let mut args = HashMap::new();
+    for arg in &instruction.args {
+        match serde_json::from_str::<serde_json::Value>(&arg.data) {
+            Ok(serde_json::Value::Object(fields)) => {
+                for (name, value) in fields {
+                    let value = match value {
+                        serde_json::Value::String(value) => value,
+                        value => value.to_string(),
+                    };
+                    args.insert(name, value);
+                }
+            }
+            _ => {
+                args.insert(arg.name.clone(), arg.data.clone());
+            }
+        }
+    }
+    args
+}
+
+fn parse_side(side: &str) -> Option<OrderSide> {
+    match transactions::enum_variant(side).as_str() {
+        "bid" | "0" => Some(OrderSide::Bid),
+        "ask" | "1" => Some(OrderSide::Ask),
+        _ => None,
+    }
+}
+
+fn lots_arg(
+    args: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<BigDecimal>, AssetWatcherError> {
+    args.get(name)
+        .map(|lots| {
+            BigDecimal::from_str(lots).map_err(|_| {
+                AssetWatcherError::PayloadParse(format!("invalid {} in order: {}", name, lots))
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+#[path = "orders_test.rs"]
+mod tests;
@@ -0,0 +1,32 @@
+use super::*;
+
+#[test]
+fn test_parse_side_names() {
+    assert_eq!(parse_side("bid"), Some(OrderSide::Bid));
+    assert_eq!(parse_side("Ask"), Some(OrderSide::Ask));
+}
+
+#[test]
+fn test_parse_side_anchor_enum_objects() {
+    assert_eq!(parse_side(r#"{"bid":{}}"#), Some(OrderSide::Bid));
+    assert_eq!(parse_side(r#"{"ask":{}}"#), Some(OrderSide::Ask));
+}
+
+#[test]
+fn test_parse_side_indexes() {
+    assert_eq!(parse_side("0"), Some(OrderSide::Bid));
+    assert_eq!(parse_side("1"), Some(OrderSide::Ask));
+}
+
+#[test]
+fn test_parse_side_unknown() {
+    assert_eq!(parse_side("2"), None);
+    assert_eq!(parse_side(""), None);
+    assert_eq!(parse_side("buy"), None);
+}
+
+#[test]
+fn test_parse_side_quoted_names() {
+    assert_eq!(parse_side(r#""bid""#), Some(OrderSide::Bid));
+    assert_eq!(parse_side(r#""ask""#), Some(OrderSide::Ask));
+}
@@ -0,0 +1,217 @@
+use bigdecimal::BigDecimal;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+use crate::entities::conditional_vaults::conditional_vaults;
+use crate::entities::markets::markets;
+use crate::entities::proposals::{proposals, Proposal, ProposalStatus};
+use crate::entities::transactions::{Instruction, Payload};
+use crate::errors::AssetWatcherError;
+
+use super::transactions;
+
+const VAULT_FINALIZED: &str = "finalized";
+const VAULT_REVERTED: &str = "reverted";
+
+/**
+ * Records a proposal from initializeProposal and links its markets to it.
+ * Markets not indexed yet fail with MissingMarket so the tx is retried once they are.
+ */
+pub async fn handle_initialize_proposal_tx(
+    pool: Pool,
+    transaction_payload: &Payload,
+    transaction_sig: String,
+) -> Result<(), AssetWatcherError> {
+    let init_instruction =
+        transactions::find_instruction(transaction_payload, "initializeProposal")?;
+    let proposal = Proposal {
+        proposal_acct: transactions::find_account(&init_instruction, &["proposal"])?,
+        dao_acct: transactions::find_account(&init_instruction, &["dao"])?,
+        proposer_acct: transactions::find_account(&init_instruction, &["proposer"])?,
+        description_url: init_instruction
+            .args
+            .iter()
+            .find(|arg| arg.name == "descriptionUrl")
+            .map(|arg| arg.data.clone()),
+        base_pass_vault_acct: transactions::find_account(&init_instruction, &["basePassVault"])
+            .ok(),
+        base_fail_vault_acct: transactions::find_account(&init_instruction, &["baseFailVault"])
+            .ok(),
+        quote_pass_vault_acct: transactions::find_account(&init_instruction, &["quotePassVault"])
+            .ok(),
+        quote_fail_vault_acct: transactions::find_account(&init_instruction, &["quoteFailVault"])
+            .ok(),
+        pass_market_acct: transactions::find_account(
+            &init_instruction,
+            &["openbookPassMarket", "passAmm"],
+        )
+        .ok(),
+        fail_market_acct: transactions::find_account(
+            &init_instruction,
+            &["openbookFailMarket", "failAmm"],
+        )
+        .ok(),
+        status: ProposalStatus::Pending,
+        init_tx_sig: transaction_sig,
+        init_slot: BigDecimal::from(transaction_payload.slot),
+        initialized_at: transactions::payload_block_time(transaction_payload)?,
+        finalize_tx_sig: None,
+        finalized_at: None,
+    };
+
+    let proposal_acct = proposal.proposal_acct.clone();
+    let mut market_accts: Vec<String> = proposal
+        .pass_market_acct
+        .iter()
+        .chain(proposal.fail_market_acct.iter())
+        .cloned()
+        .collect();
+    // the twap markets of openbook based proposals belong to it as well
+    market_accts.extend(
+        ["openbookTwapPassMarket", "openbookTwapFailMarket"]
+            .iter()
+            .filter_map(|name| transactions::find_account(&init_instruction, &[name]).ok()),
+    );
+
+    let missing_markets = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            // a finalized proposal keeps its status when the init tx is indexed again
+            diesel::insert_into(proposals::table)
+                .values(&proposal)
+                .on_conflict(proposals::proposal_acct)
+                .do_nothing()
+                .execute(db)?;
+            diesel::update(markets::table.filter(markets::market_acct.eq_any(&market_accts)))
+                .set(markets::proposal_acct.eq(&proposal_acct))
+                .execute(db)?;
+            let linked: Vec<String> = markets::table
+                .select(markets::market_acct)
+                .filter(markets::market_acct.eq_any(&market_accts))
+                .load(db)?;
+            Ok::<_, diesel::result::Error>(
+                market_accts
+                    .into_iter()
+                    .filter(|acct| !linked.contains(acct))
+                    .collect::<Vec<String>>(),
+            )
+        })
+        .await??;
+
+    if !missing_markets.is_empty() {
+        return Err(AssetWatcherError::MissingMarket(missing_markets.join(", ")));
+    }
+    Ok(())
+}
+
+/**
+ * Settles a proposal from finalizeProposal. Each conditional vault gets the status its
+ * settleConditionalVault inner instruction set, finalized or reverted, and the proposal
+ * passed if its pass vaults were finalized.
+ */
+pub async fn handle_finalize_proposal_tx(
+    pool: Pool,
+    transaction_payload: &Payload,
+    transaction_sig: String,
+) -> Result<(), AssetWatcherError> {
+    let finalize_instruction =
+        transactions::find_instruction(transaction_payload, "finalizeProposal")?;
+    let proposal_acct = transactions::find_account(&finalize_instruction, &["proposal"])?;
+    let finalized_at = transactions::payload_block_time(transaction_payload)?;
+    let settlements = transaction_payload
+        .instructions
+        .iter()
+        .filter(|instruction| instruction.name == "settleConditionalVault")
+        .map(|instruction| {
+            Ok((
+                transactions::find_account(instruction, &["vault"])?,
+                vault_status(instruction)?,
+            ))
+        })
+        .collect::<Result<Vec<(String, &'static str)>, AssetWatcherError>>()?;
+    if settlements.is_empty() {
+        return Err(AssetWatcherError::PayloadParse(
+            "settleConditionalVault not found in finalizeProposal tx".to_string(),
+        ));
+    }
+
+    let proposal_acct_clone = proposal_acct.clone();
+    let (missing_vaults, proposal_found) = pool
+        .get()
+        .await?
+        .interact(move |db| {
+            db.transaction(|db| {
+                let mut missing_vaults = vec![];
+                for (vault_acct, new_status) in &settlements {
+                    let updated = diesel::update(conditional_vaults::table.find(vault_acct))
+                        .set(conditional_vaults::status.eq(*new_status))
+                        .execute(db)?;
+                    if updated == 0 {
+                        missing_vaults.push(vault_acct.clone());
+                    }
+                }
+
+                let Some(proposal) = proposals::table
+                    .find(&proposal_acct_clone)
+                    .first::<Proposal>(db)
+                    .optional()?
+                else {
+                    return Ok((missing_vaults, false));
+                };
+                let pass_vaults = [
+                    &proposal.base_pass_vault_acct,
+                    &proposal.quote_pass_vault_acct,
+                ];
+                let fail_vaults = [
+                    &proposal.base_fail_vault_acct,
+                    &proposal.quote_fail_vault_acct,
+                ];
+                let passed = settlements.iter().any(|(vault_acct, new_status)| {
+                    let vault_acct = Some(vault_acct.clone());
+                    (*new_status == VAULT_FINALIZED && pass_vaults.contains(&&vault_acct))
+                        || (*new_status == VAULT_REVERTED && fail_vaults.contains(&&vault_acct))
+                });
+                let proposal_status = if passed {
+                    ProposalStatus::Passed
+                } else {
+                    ProposalStatus::Failed
+                };
+                diesel::update(proposals::table.find(&proposal_acct_clone))
+                    .set((
+                        proposals::status.eq(proposal_status),
+                        proposals::finalize_tx_sig.eq(Some(transaction_sig)),
+                        proposals::finalized_at.eq(Some(finalized_at)),
+                    ))
+                    .execute(db)?;
+                Ok::<_, diesel::result::Error>((missing_vaults, true))
+            })
+        })
+        .await??;
+
+    if !missing_vaults.is_empty() {
+        return Err(AssetWatcherError::MissingConditionalVault(
+            missing_vaults.join(", "),
+        ));
+    }
+    if !proposal_found {
+        return Err(AssetWatcherError::MissingProposal(proposal_acct));
+    }
+    Ok(())
+}
+
+fn vault_status(settle_instruction: &Instruction) -> Result<&'static str, AssetWatcherError> {
+    let new_status = transactions::enum_arg(settle_instruction, "newStatus").unwrap_or_default();
+    match new_status.as_str() {
+        VAULT_FINALIZED => Ok(VAULT_FINALIZED),
+        VAULT_REVERTED => Ok(VAULT_REVERTED),
+        _ => Err(AssetWatcherError::PayloadParse(format!(
+            "invalid newStatus in settleConditionalVault: {:?}",
+            new_status
+        ))),
+    }
+}
+
+#[cfg(test)]
+#[path = "proposals_test.rs"]
+mod tests;
@@ -0,0 +1,54 @@
+use super::*;
+
+fn settle_instruction(new_status: Option<&str>) -> Instruction {
+    let args: Vec<serde_json::Value> = new_status
+        .map(|data| serde_json::json!({ "name": "newStatus", "type": "VaultStatus", "data": data }))
+        .into_iter()
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "name": "settleConditionalVault",
+        "stackHeight": 1,
+        "programIdIndex": 0,
+        "data": "",
+        "accounts": [],
+        "accountsWithData": [],
+        "args": args,
+    }))
+    .expect("instruction should deserialize")
+}
+
+#[test]
+fn test_vault_status_names() {
+    assert_eq!(
+        vault_status(&settle_instruction(Some("Finalized"))).unwrap(),
+        VAULT_FINALIZED
+    );
+    assert_eq!(
+        vault_status(&settle_instruction(Some("reverted"))).unwrap(),
+        VAULT_REVERTED
+    );
+}
+
+#[test]
+fn test_vault_status_anchor_enum_objects() {
+    assert_eq!(
+        vault_status(&settle_instruction(Some(r#"{"finalized":{}}"#))).unwrap(),
+        VAULT_FINALIZED
+    );
+    assert_eq!(
+        vault_status(&settle_instruction(Some(r#"{"reverted":{}}"#))).unwrap(),
+        VAULT_REVERTED
+    );
+}
+
+#[test]
+fn test_vault_status_unknown_or_missing() {
+    assert!(matches!(
+        vault_status(&settle_instruction(Some(r#"{"active":{}}"#))),
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+    assert!(matches!(
+        vault_status(&settle_instruction(None)),
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+}
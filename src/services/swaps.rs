@@ -1,48 +1,34 @@
-use std::io;
-use std::sync::Arc;
-
+use crate::entities::swaps::{swaps, Swap, SwapDirection};
 use crate::entities::transactions::Instruction;
 use crate::entities::transactions::Payload;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
-use diesel::PgConnection;
+use crate::errors::AssetWatcherError;
+use bigdecimal::{BigDecimal, Zero};
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
 
-use super::{balances,transactions};
+use super::{balances, transactions};
 
 pub async fn handle_swap_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     transaction_payload: &Payload,
     transaction_sig: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let swap_instruction = find_swap_instruction(&transaction_payload)?;
+) -> Result<(), AssetWatcherError> {
+    let swap_instruction = find_swap_instruction(transaction_payload)?;
     let user_account = transactions::find_user_account(&swap_instruction)?;
-    let amm_acct = swap_instruction
-        .accounts_with_data
-        .iter()
-        .find(|account| account.name == "amm")
-        .map(|account| account.pubkey.clone());
-
-    let amm_acct_str = match amm_acct {
-        Some(acct) => acct,
-        None => String::default().to_string(),
-    };
-
-    if amm_acct_str == "" {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "amm_acct not found",
-        )));
-    }
+    let amm_acct_str = transactions::find_account(&swap_instruction, &["amm"])?;
 
     let (base_mint, quote_mint) =
-        transactions::find_base_and_quote_mint(amm_acct_str, Arc::clone(&conn_manager)).await?;
+        transactions::find_base_and_quote_mint(amm_acct_str.clone(), pool.clone()).await?;
 
-    let relevant_accounts =
-        transactions::get_relevant_accounts_from_ix_and_mints(&swap_instruction, base_mint, quote_mint);
+    let relevant_accounts = transactions::get_relevant_accounts_from_ix_and_mints(
+        &swap_instruction,
+        base_mint.clone(),
+        quote_mint.clone(),
+    );
 
     for (token_account, mint_acct_value) in relevant_accounts {
         balances::handle_token_acct_in_tx(
-            Arc::clone(&conn_manager),
+            pool.clone(),
             transaction_payload,
             transaction_sig.clone(),
             &mint_acct_value,
@@ -52,16 +38,134 @@ pub async fn handle_swap_tx(
         .await?
     }
 
-    Ok(())
+    let swap = swap_record(
+        transaction_payload,
+        &swap_instruction,
+        transaction_sig,
+        user_account,
+        amm_acct_str,
+        base_mint,
+        quote_mint,
+    )?;
+    record_swap(pool, swap).await
 }
 
-fn find_swap_instruction(
-    transaction_payload: &Payload,
-) -> Result<Instruction, Box<dyn std::error::Error>> {
+fn find_swap_instruction(transaction_payload: &Payload) -> Result<Instruction, AssetWatcherError> {
     transaction_payload
         .instructions
         .iter()
         .find(|instruction| instruction.name == "swap")
         .cloned()
-        .ok_or_else(|| "swap instruction not found".into())
-}
\ No newline at end of file
+        .ok_or_else(|| AssetWatcherError::PayloadParse("swap instruction not found".to_string()))
+}
+
+/**
+ * The trade made by the swap. The input amount is exact in the instruction args, while a mint
+ * in the same tx can move the input acct too; the output amount is only known from the output
+ * acct's balance change. outputAmountMin is just the slippage floor, so a swap without output
+ * token balances is a payload parse error rather than a record with a made up amount and price.
+ */
+fn swap_record(
+    transaction_payload: &Payload,
+    swap_instruction: &Instruction,
+    transaction_sig: String,
+    user_acct: String,
+    amm_acct: String,
+    base_mint: String,
+    quote_mint: String,
+) -> Result<Swap, AssetWatcherError> {
+    let base_acct = transactions::find_account(swap_instruction, &["userBaseAccount"])?;
+    let quote_acct = transactions::find_account(swap_instruction, &["userQuoteAccount"])?;
+    let direction = swap_direction(transaction_payload, swap_instruction, &base_acct)?;
+    let (input_acct, output_acct, input_mint_acct, output_mint_acct) = match direction {
+        SwapDirection::Buy => (&quote_acct, &base_acct, quote_mint, base_mint),
+        SwapDirection::Sell => (&base_acct, &quote_acct, base_mint, quote_mint),
+    };
+
+    let input_amount = match transactions::amount_arg(swap_instruction, "inputAmount")? {
+        Some(amount) => amount,
+        None => -transactions::token_balance_change(transaction_payload, input_acct)?.ok_or_else(
+            || {
+                AssetWatcherError::PayloadParse(
+                    "no inputAmount arg or input token balances in swap".to_string(),
+                )
+            },
+        )?,
+    };
+    let output_amount = transactions::token_balance_change(transaction_payload, output_acct)?
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse("no output token balances in swap".to_string())
+        })?;
+
+    let (base_amount, quote_amount) = match direction {
+        SwapDirection::Buy => (&output_amount, &input_amount),
+        SwapDirection::Sell => (&input_amount, &output_amount),
+    };
+    let price = match (
+        transactions::token_decimals(transaction_payload, &base_acct),
+        transactions::token_decimals(transaction_payload, &quote_acct),
+    ) {
+        (Some(base_decimals), Some(quote_decimals)) if !base_amount.is_zero() => Some(
+            ((quote_amount * pow10(base_decimals)) / (base_amount * pow10(quote_decimals)))
+                .round(12),
+        ),
+        _ => None,
+    };
+
+    Ok(Swap {
+        swap_tx_sig: transaction_sig,
+        market_acct: amm_acct,
+        user_acct,
+        direction,
+        input_mint_acct,
+        output_mint_acct,
+        input_amount,
+        output_amount,
+        price,
+        slot: BigDecimal::from(transaction_payload.slot),
+        block_time: transactions::payload_block_time(transaction_payload)?,
+    })
+}
+
+// without a swap type arg the direction follows from whether the user's base balance went up
+fn swap_direction(
+    transaction_payload: &Payload,
+    swap_instruction: &Instruction,
+    base_acct: &str,
+) -> Result<SwapDirection, AssetWatcherError> {
+    match transactions::enum_arg(swap_instruction, "swapType").as_deref() {
+        Some("buy") => return Ok(SwapDirection::Buy),
+        Some("sell") => return Ok(SwapDirection::Sell),
+        _ => (),
+    }
+    match transactions::token_balance_change(transaction_payload, base_acct)? {
+        Some(change) if change > BigDecimal::zero() => Ok(SwapDirection::Buy),
+        Some(change) if change < BigDecimal::zero() => Ok(SwapDirection::Sell),
+        _ => Err(AssetWatcherError::PayloadParse(
+            "could not tell the swap direction".to_string(),
+        )),
+    }
+}
+
+fn pow10(exponent: u8) -> BigDecimal {
+    BigDecimal::new(1.into(), -i64::from(exponent))
+}
+
+async fn record_swap(pool: Pool, swap: Swap) -> Result<(), AssetWatcherError> {
+    pool.get()
+        .await?
+        .interact(move |db| {
+            diesel::insert_into(swaps::table)
+                .values(&swap)
+                .on_conflict(swaps::swap_tx_sig)
+                .do_update()
+                .set(&swap)
+                .execute(db)
+        })
+        .await??;
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "swaps_test.rs"]
+mod tests;
@@ -0,0 +1,114 @@
+use super::*;
+
+const BASE_ACCT: &str = "base_acct";
+
+// payload amounts are serialized with their type, e.g. "BigInt:100"
+fn token_balance(amount: &str) -> serde_json::Value {
+    serde_json::json!({
+        "mint": "base_mint",
+        "owner": "user",
+        "amount": format!("BigInt:{}", amount),
+        "decimals": 6,
+    })
+}
+
+// a swap tx where the user's base acct went from `pre` to `post`, if given
+fn swap_tx(swap_type: Option<&str>, base_balances: Option<(&str, &str)>) -> (Payload, Instruction) {
+    let args: Vec<serde_json::Value> = swap_type
+        .map(|data| serde_json::json!({ "name": "swapType", "type": "SwapType", "data": data }))
+        .into_iter()
+        .collect();
+    let instruction = serde_json::json!({
+        "name": "swap",
+        "stackHeight": 1,
+        "programIdIndex": 0,
+        "data": "",
+        "accounts": [],
+        "accountsWithData": [],
+        "args": args,
+    });
+    let accounts: Vec<serde_json::Value> = base_balances
+        .map(|(pre, post)| {
+            serde_json::json!({
+                "name": "",
+                "pubkey": BASE_ACCT,
+                "isSigner": false,
+                "isWriteable": true,
+                "preBalance": null,
+                "postBalance": null,
+                "preTokenBalance": token_balance(pre),
+                "postTokenBalance": token_balance(post),
+            })
+        })
+        .into_iter()
+        .collect();
+    let payload = serde_json::json!({
+        "blockTime": 0,
+        "slot": 1,
+        "recentBlockhash": "",
+        "computeUnitsConsumed": "0",
+        "fee": "0",
+        "signatures": [],
+        "logMessages": [],
+        "accounts": accounts,
+        "instructions": [instruction.clone()],
+    });
+    (
+        serde_json::from_value(payload).expect("payload should deserialize"),
+        serde_json::from_value(instruction).expect("instruction should deserialize"),
+    )
+}
+
+#[test]
+fn test_swap_direction_from_swap_type_arg() {
+    let (payload, instruction) = swap_tx(Some(r#"{"buy":{}}"#), None);
+    assert_eq!(
+        swap_direction(&payload, &instruction, BASE_ACCT).unwrap(),
+        SwapDirection::Buy
+    );
+
+    let (payload, instruction) = swap_tx(Some("Sell"), None);
+    assert_eq!(
+        swap_direction(&payload, &instruction, BASE_ACCT).unwrap(),
+        SwapDirection::Sell
+    );
+}
+
+#[test]
+fn test_swap_type_arg_wins_over_balances() {
+    let (payload, instruction) = swap_tx(Some(r#"{"buy":{}}"#), Some(("100", "50")));
+    assert_eq!(
+        swap_direction(&payload, &instruction, BASE_ACCT).unwrap(),
+        SwapDirection::Buy
+    );
+}
+
+#[test]
+fn test_swap_direction_falls_back_to_base_balance_change() {
+    let (payload, instruction) = swap_tx(None, Some(("50", "100")));
+    assert_eq!(
+        swap_direction(&payload, &instruction, BASE_ACCT).unwrap(),
+        SwapDirection::Buy
+    );
+
+    let (payload, instruction) = swap_tx(None, Some(("100", "50")));
+    assert_eq!(
+        swap_direction(&payload, &instruction, BASE_ACCT).unwrap(),
+        SwapDirection::Sell
+    );
+}
+
+#[test]
+fn test_swap_direction_unknown_without_arg_or_balance_change() {
+    let (payload, instruction) = swap_tx(None, None);
+    assert!(matches!(
+        swap_direction(&payload, &instruction, BASE_ACCT),
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+
+    let (payload, instruction) = swap_tx(None, Some(("100", "100")));
+    assert!(matches!(
+        swap_direction(&payload, &instruction, BASE_ACCT),
+        Err(AssetWatcherError::PayloadParse(_))
+    ));
+}
@@ -1,11 +1,9 @@
-use std::sync::Arc;
-
-use bigdecimal::BigDecimal;
-use chrono::Utc;
-use deadpool::managed::Object;
-use deadpool_diesel::Manager;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
-use diesel::PgConnection;
+use diesel::sql_types::{Integer, Text};
+use std::str::FromStr;
 
 use crate::entities::conditional_vaults::conditional_vaults::dsl::*;
 use crate::entities::conditional_vaults::ConditionalVault;
@@ -17,113 +15,129 @@ use crate::entities::token_acct_balances::TokenAcctBalances;
 use crate::entities::token_accts::token_accts;
 use crate::entities::transactions::Instruction;
 use crate::entities::transactions::Payload;
+use crate::entities::transactions::TokenBalance;
+use crate::errors::AssetWatcherError;
 // use crate::entrypoints::events;
 
+// first key of the advisory lock taken per token acct, so it cannot clash with other advisory locks
+const TOKEN_ACCT_LOCK_NAMESPACE: i32 = 1;
+
 /**
  * Handles updating our DB for a tx that affects a token acct balance.
- * Will update both token_accts and token_acct_balances table with the new balance amount
+ * Will update both token_accts and token_acct_balances table with the new balance amount.
+ *
+ * Everything happens in one db transaction holding an advisory lock on the token acct, so
+ * concurrent writers (account updates, indexed txs, backfills, other watchers) for the same acct
+ * take turns instead of reading the same previous balance. The delta is taken against the
+ * previous row by slot; when an older balance lands late, the row after it is re-based too.
  */
 pub async fn handle_token_acct_balance_tx(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     token_acct: String,
     new_balance: BigDecimal,
     transaction_sig: Option<String>,
     slot: BigDecimal,
     mint_acct: String,
     owner_acct: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Query the most recent value for the given token_acct to calculate the delta
-    let token_acct_clone_1 = token_acct.clone();
-    let previous_balance: Option<_> = conn_manager
+) -> Result<(), AssetWatcherError> {
+    pool.get()
+        .await?
         .interact(move |db| {
-            token_acct_balances::table
-                .filter(token_acct_balances::token_acct.eq(token_acct_clone_1))
-                .order(token_acct_balances::slot.desc())
-                .select(token_acct_balances::amount)
-                .first::<BigDecimal>(db)
-                .optional()
-        })
-        .await??;
+            db.transaction(|db| {
+                diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+                    .bind::<Integer, _>(TOKEN_ACCT_LOCK_NAMESPACE)
+                    .bind::<Text, _>(&token_acct)
+                    .execute(db)?;
 
-    let delta = match previous_balance {
-        Some(prev_amount) => new_balance.clone() - prev_amount,
-        None => new_balance.clone(),
-    };
+                let existing_balance = token_acct_balances::table
+                    .filter(
+                        token_acct_balances::slot
+                            .eq(&slot)
+                            .and(token_acct_balances::token_acct.eq(&token_acct)),
+                    )
+                    .first::<TokenAcctBalances>(db)
+                    .optional()?;
 
-    let token_acct_clone_2 = token_acct.clone();
-    
-    let slot_dec = BigDecimal::from(slot.clone());
-    let slot_dec_clone = BigDecimal::from(slot.clone());    
-    
-    let existing_balance_res = conn_manager
-        .interact(move |db| {
-            token_acct_balances::table
-                .filter(
-                    token_acct_balances::slot
-                        .eq(&slot_dec)
-                        .and(token_acct_balances::token_acct.eq(token_acct_clone_2)),
-                )
-                .first::<TokenAcctBalances>(db)
-        })
-        .await?;
+                match existing_balance {
+                    Some(balance) => {
+                        if balance.tx_sig.is_none() {
+                            diesel::update(
+                                token_acct_balances::table.filter(
+                                    token_acct_balances::token_acct
+                                        .eq(&token_acct)
+                                        .and(token_acct_balances::slot.eq(&slot)),
+                                ),
+                            )
+                            .set(token_acct_balances::tx_sig.eq(&transaction_sig))
+                            .execute(db)?;
+                        }
+                        // already has the correct tx_sig, no need to update anything
+                    }
+                    None => {
+                        let previous_balance = token_acct_balances::table
+                            .filter(token_acct_balances::token_acct.eq(&token_acct))
+                            .filter(token_acct_balances::slot.lt(&slot))
+                            .order(token_acct_balances::slot.desc())
+                            .select(token_acct_balances::amount)
+                            .first::<BigDecimal>(db)
+                            .optional()?;
+                        let delta = match previous_balance {
+                            Some(prev_amount) => &new_balance - prev_amount,
+                            None => new_balance.clone(),
+                        };
 
-    let maybe_balance = existing_balance_res.ok();
-    
-    
-    if let Some(balance) = maybe_balance {
-        if balance.tx_sig.is_none() {
-            let token_acct_clone_3 = token_acct.clone();
-            let tx_sig_clone = transaction_sig.clone();
-            conn_manager
-                .interact(move |db| {
+                        diesel::insert_into(token_acct_balances::table)
+                            .values(&TokenAcctBalances {
+                                token_acct: token_acct.clone(),
+                                mint_acct,
+                                owner_acct,
+                                amount: new_balance.clone(),
+                                delta,
+                                slot: slot.clone(),
+                                tx_sig: transaction_sig,
+                                created_at: Utc::now(),
+                            })
+                            .execute(db)?;
+
+                        let next_balance = token_acct_balances::table
+                            .filter(token_acct_balances::token_acct.eq(&token_acct))
+                            .filter(token_acct_balances::slot.gt(&slot))
+                            .order(token_acct_balances::slot.asc())
+                            .select((token_acct_balances::slot, token_acct_balances::amount))
+                            .first::<(BigDecimal, BigDecimal)>(db)
+                            .optional()?;
+                        if let Some((next_slot, next_amount)) = next_balance {
+                            diesel::update(
+                                token_acct_balances::table.filter(
+                                    token_acct_balances::token_acct
+                                        .eq(&token_acct)
+                                        .and(token_acct_balances::slot.eq(next_slot)),
+                                ),
+                            )
+                            .set(token_acct_balances::delta.eq(next_amount - &new_balance))
+                            .execute(db)?;
+                        }
+                    }
+                }
+
+                // token_accts.amount only follows the newest balance we have
+                let newer_balances = token_acct_balances::table
+                    .filter(token_acct_balances::token_acct.eq(&token_acct))
+                    .filter(token_acct_balances::slot.gt(&slot))
+                    .count()
+                    .get_result::<i64>(db)?;
+                if newer_balances == 0 {
                     diesel::update(
-                        token_acct_balances::table.filter(
-                            token_acct_balances::token_acct
-                                .eq(token_acct_clone_3)
-                                .and(token_acct_balances::slot.eq(&slot_dec_clone)),
-                        ),
+                        token_accts::table.filter(token_accts::token_acct.eq(&token_acct)),
                     )
-                    .set(token_acct_balances::tx_sig.eq(tx_sig_clone))
-                    .execute(db)
-                })
-                .await??;
-        }
-        // already has the correct tx_sig, no need to update anything
-    } else {
-        let new_balance_clone = new_balance.clone();
-        let new_token_acct_balance = TokenAcctBalances {
-            token_acct: token_acct.clone(),
-            mint_acct: mint_acct,
-            owner_acct: owner_acct,
-            amount: BigDecimal::from(new_balance_clone),
-            delta,
-            slot: slot,
-            tx_sig: transaction_sig,
-            created_at: Utc::now(),
-        };
-
-        conn_manager
-            .interact(move |db| {
-                diesel::insert_into(token_acct_balances::table)
-                    .values(&new_token_acct_balance)
-                    .execute(db)
+                    .set((
+                        token_accts::amount.eq(&new_balance),
+                        token_accts::dsl::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(db)?;
+                }
+                Ok::<_, diesel::result::Error>(())
             })
-            .await??;
-    }
-
-    // Update the token_accts table with the new balance in the amount column
-    let token_acct_clone_4 = token_acct.clone();
-    let new_balance_clone = new_balance.clone();
-    conn_manager
-        .interact(move |db| {
-            diesel::update(
-                token_accts::table.filter(token_accts::token_acct.eq(token_acct_clone_4)),
-            )
-            .set((
-                token_accts::amount.eq(new_balance_clone),
-                token_accts::dsl::updated_at.eq(Utc::now()),
-            ))
-            .execute(db)
         })
         .await??;
 
@@ -133,62 +147,73 @@ pub async fn handle_token_acct_balance_tx(
 pub fn find_instruction(
     transaction_payload: &Payload,
     instruction_name: &str,
-) -> Result<Instruction, Box<dyn std::error::Error>> {
+) -> Result<Instruction, AssetWatcherError> {
     transaction_payload
         .instructions
         .iter()
-        .find(|instruction| instruction.name == instruction_name.to_string())
+        .find(|instruction| instruction.name == instruction_name)
         .cloned()
-        .ok_or_else(|| "Instruction not found".into())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(format!("{} instruction not found", instruction_name))
+        })
 }
 
-pub fn find_user_account(
-    swap_instruction: &Instruction,
-) -> Result<String, Box<dyn std::error::Error>> {
+pub fn find_user_account(swap_instruction: &Instruction) -> Result<String, AssetWatcherError> {
     swap_instruction
         .accounts_with_data
         .iter()
         .find(|account| account.name == "user")
         .map(|account| account.pubkey.clone())
-        .ok_or_else(|| "User account not found in swap instruction".into())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(
+                "User account not found in swap instruction".to_string(),
+            )
+        })
 }
 
-pub fn find_authority_account(
-    mint_instruction: &Instruction,
-) -> Result<String, Box<dyn std::error::Error>> {
+pub fn find_authority_account(mint_instruction: &Instruction) -> Result<String, AssetWatcherError> {
     mint_instruction
         .accounts_with_data
         .iter()
         .find(|account| account.name == "authority")
         .map(|account| account.pubkey.clone())
-        .ok_or_else(|| "Authority account not found in mintConditionalTokens instruction".into())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(
+                "Authority account not found in mintConditionalTokens instruction".to_string(),
+            )
+        })
 }
 
-pub fn find_vault_account(
-    mint_instruction: &Instruction,
-) -> Result<String, Box<dyn std::error::Error>> {
+pub fn find_vault_account(mint_instruction: &Instruction) -> Result<String, AssetWatcherError> {
     mint_instruction
         .accounts_with_data
         .iter()
         .find(|account| account.name == "vault")
         .map(|account| account.pubkey.clone())
-        .ok_or_else(|| "Vault account not found in mintConditionalTokens instruction".into())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(
+                "Vault account not found in mintConditionalTokens instruction".to_string(),
+            )
+        })
 }
 
 pub async fn get_conditional_vault(
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
+    pool: Pool,
     vault_account: &str,
-) -> Result<ConditionalVault, Box<dyn std::error::Error>> {
+) -> Result<ConditionalVault, AssetWatcherError> {
     let vault_acct_clone = vault_account.to_string();
-    let vault = conn_manager
+    let vault = pool
+        .get()
+        .await?
         .interact(move |connection| {
             conditional_vaults
                 .filter(cond_vault_acct.eq(vault_acct_clone))
                 .first(connection)
+                .optional()
         })
         .await??;
 
-    Ok(vault)
+    vault.ok_or_else(|| AssetWatcherError::MissingConditionalVault(vault_account.to_string()))
 }
 
 pub fn get_relevant_accounts_from_mint_and_vault<'a>(
@@ -242,15 +267,143 @@ pub fn get_relevant_accounts_from_ix_and_mints(
 
 pub async fn find_base_and_quote_mint(
     amm_acct: String,
-    conn_manager: Arc<Object<Manager<PgConnection>>>,
-) -> Result<(String, String), Box<dyn std::error::Error>> {
-    let amm_market: Market = conn_manager
+    pool: Pool,
+) -> Result<(String, String), AssetWatcherError> {
+    let amm_acct_clone = amm_acct.clone();
+    let amm_market: Market = pool
+        .get()
+        .await?
         .interact(|connection| {
             markets::table
-                .filter(market_acct.eq(amm_acct))
+                .filter(market_acct.eq(amm_acct_clone))
                 .first(connection)
+                .optional()
         })
-        .await??;
+        .await??
+        .ok_or(AssetWatcherError::MissingMarket(amm_acct))?;
 
     Ok((amm_market.base_mint_acct, amm_market.quote_mint_acct))
 }
+
+/// When the tx was included, from the payload's unix block time.
+pub fn payload_block_time(
+    transaction_payload: &Payload,
+) -> Result<DateTime<Utc>, AssetWatcherError> {
+    DateTime::from_timestamp(transaction_payload.block_time, 0).ok_or_else(|| {
+        AssetWatcherError::PayloadParse(format!(
+            "invalid block time: {}",
+            transaction_payload.block_time
+        ))
+    })
+}
+
+/// The first of the instruction's accounts named any of `names`, for accounts renamed across program versions.
+pub fn find_account(
+    instruction: &Instruction,
+    names: &[&str],
+) -> Result<String, AssetWatcherError> {
+    instruction
+        .accounts_with_data
+        .iter()
+        .find(|account| names.contains(&account.name.as_str()))
+        .map(|account| account.pubkey.clone())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(format!(
+                "{} account not found in {} instruction",
+                names[0], instruction.name
+            ))
+        })
+}
+
+/// How much the token acct's balance changed in the tx, or None if the payload has no token balances for it.
+pub fn token_balance_change(
+    transaction_payload: &Payload,
+    token_acct: &str,
+) -> Result<Option<BigDecimal>, AssetWatcherError> {
+    let Some(account) = transaction_payload
+        .accounts
+        .iter()
+        .find(|account| account.pubkey == token_acct)
+        .filter(|account| {
+            account.pre_token_balance.is_some() || account.post_token_balance.is_some()
+        })
+    else {
+        return Ok(None);
+    };
+    Ok(Some(
+        token_amount(&account.post_token_balance)? - token_amount(&account.pre_token_balance)?,
+    ))
+}
+
+/// Decimals of the token acct's mint, as recorded in the payload's token balances.
+pub fn token_decimals(transaction_payload: &Payload, token_acct: &str) -> Option<u8> {
+    transaction_payload
+        .accounts
+        .iter()
+        .find(|account| account.pubkey == token_acct)
+        .and_then(|account| {
+            account
+                .post_token_balance
+                .as_ref()
+                .or(account.pre_token_balance.as_ref())
+        })
+        .map(|token_balance| token_balance.decimals)
+}
+
+fn token_amount(token_balance: &Option<TokenBalance>) -> Result<BigDecimal, AssetWatcherError> {
+    let Some(token_balance) = token_balance else {
+        return Ok(BigDecimal::zero());
+    };
+    token_balance
+        .amount
+        .split(':')
+        .nth(1)
+        .and_then(|amount| BigDecimal::from_str(amount).ok())
+        .ok_or_else(|| {
+            AssetWatcherError::PayloadParse(format!(
+                "invalid token balance format: {}",
+                token_balance.amount
+            ))
+        })
+}
+
+/**
+ * The lowercased variant name of an enum arg's data. Anchor serializes an enum as an object like
+ * {"bid":{}}, while some payloads carry the plain name, quoted or not, or the variant's index.
+ */
+pub fn enum_variant(data: &str) -> String {
+    let variant = match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(serde_json::Value::Object(fields)) if fields.len() == 1 => {
+            fields.keys().next().cloned().unwrap_or_default()
+        }
+        Ok(serde_json::Value::String(name)) => name,
+        _ => data.trim().to_string(),
+    };
+    variant.to_lowercase()
+}
+
+/// The variant name of an enum instruction arg, see `enum_variant`, if the instruction has it.
+pub fn enum_arg(instruction: &Instruction, name: &str) -> Option<String> {
+    instruction
+        .args
+        .iter()
+        .find(|arg| arg.name == name)
+        .map(|arg| enum_variant(&arg.data))
+}
+
+/// A numeric instruction arg, such as an amount, if the instruction has it.
+pub fn amount_arg(
+    instruction: &Instruction,
+    name: &str,
+) -> Result<Option<BigDecimal>, AssetWatcherError> {
+    instruction
+        .args
+        .iter()
+        .find(|arg| arg.name == name)
+        .map(|arg| {
+            BigDecimal::from_str(&arg.data).map_err(|_| {
+                AssetWatcherError::PayloadParse(format!("invalid {} arg: {}", name, arg.data))
+            })
+        })
+        .transpose()
+}
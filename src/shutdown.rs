@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+use tokio_util::task::TaskTracker;
+
+/**
+ * Shared shutdown signal plus a tracker for every task doing work we want to let finish.
+ * Long running loops select on `cancelled()`; units of work are spawned with `spawn` so
+ * `drain` can wait for them (bounded by a timeout) before the process exits.
+ */
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn cancelled(&self) -> WaitForCancellationFuture<'_> {
+        self.token.cancelled()
+    }
+
+    /// A token cancelled on shutdown that can also be cancelled on its own, for work with a shorter lifetime.
+    pub fn child_token(&self) -> CancellationToken {
+        self.token.child_token()
+    }
+
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.tracker.spawn(task)
+    }
+
+    /// Waits for tracked tasks to finish. Returns false if the timeout hit first.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tracker.close();
+        tokio::time::timeout(timeout, self.tracker.wait())
+            .await
+            .is_ok()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.tracker.len()
+    }
+}